@@ -0,0 +1,674 @@
+use crate::error::{AppError, AppResult, Device};
+use crate::events::{HallSampleEvent, LaserProfileEvent, LaserSummaryEvent, MotorPositionEvent, Progress, Throttle};
+use crate::serial::{hall_to_volts, laser_parse_data, HallStat, LaserData};
+use crate::sqlite::{
+    connect_to_db, insert_data, insert_data_stat, set_project_status, set_project_verdict, PROJECT_ABORTED,
+    PROJECT_FINISHED,
+};
+use crate::verdict::{Verdict, VerdictResult, WearAccumulator};
+use crate::{AppWrapper, Payload};
+use chrono::{DateTime, Local};
+use log::{debug, error, info, warn};
+use rusqlite::Connection;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, watch};
+
+/// 同步器最多等待多少个角度，超过后仍未到齐的采样视为缺失
+const PENDING_WINDOW: u64 = 32;
+const CHANNEL_SIZE: usize = 64;
+/// 每个角度霍尔采样次数的上限，避免单个角度停留过久
+pub const MAX_HALL_SAMPLES: u32 = 32;
+/// 霍尔数据噪声超限时同一角度最多重新采样的次数
+const HALL_RESAMPLE_RETRIES: u32 = 2;
+
+/// 电机到达某一角度时广播给各传感器任务的触发信号
+#[derive(Clone, Copy)]
+pub struct AngleTrigger {
+    pub seq: u64,
+    pub angle: f32,
+    /// 到达该角度的时间，同一角度的各传感器数据共用
+    pub time: DateTime<Local>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sensor {
+    Hall,
+    Laser,
+}
+
+/// 传感器任务产出的采样，按触发序号与角度对应
+pub enum SensorSample {
+    Hall {
+        seq: u64,
+        stat: HallStat,
+    },
+    Laser {
+        seq: u64,
+        points: Vec<LaserData>,
+    },
+    /// 采集失败，同步器据此结束对该角度的等待
+    Missing {
+        seq: u64,
+        sensor: Sensor,
+        error: AppError,
+    },
+}
+
+/// 一个角度上对齐后的各传感器数据，缺失的传感器为空
+pub struct AngleRecord {
+    pub angle: f32,
+    pub time: DateTime<Local>,
+    pub hall: Option<HallStat>,
+    pub laser: Option<Vec<LaserData>>,
+}
+
+pub struct ScanConfig {
+    pub parent_id: i64,
+    pub samples: u32,
+    pub max_std: Option<f32>,
+    pub laser_d: f32,
+    /// 霍尔原始数据和电压数据文件，未启用霍尔时为空
+    pub hall_paths: Option<(String, String)>,
+    /// 激光点云文件，未启用激光时为空
+    pub laser_path: Option<String>,
+    /// 本次检测开始的抓包文件，结束时关闭；已有手动记录时为空
+    pub capture: Option<PathBuf>,
+}
+
+impl ScanConfig {
+    pub fn use_hall(&self) -> bool {
+        self.hall_paths.is_some()
+    }
+
+    pub fn use_laser(&self) -> bool {
+        self.laser_path.is_some()
+    }
+}
+
+/// 一个角度上各传感器的到达情况，缺失的传感器也记为已到达
+struct Pending {
+    record: AngleRecord,
+    hall: bool,
+    laser: bool,
+}
+
+/// 按触发序号把各传感器的采样合并为每个角度一条记录，并按序号顺序输出
+pub struct Joiner {
+    use_hall: bool,
+    use_laser: bool,
+    pending: BTreeMap<u64, Pending>,
+}
+
+impl Joiner {
+    pub fn new(use_hall: bool, use_laser: bool) -> Self {
+        Joiner {
+            use_hall,
+            use_laser,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, trigger: &AngleTrigger) {
+        // 未启用的传感器视为已到达
+        let pending = Pending {
+            record: AngleRecord {
+                angle: trigger.angle,
+                time: trigger.time,
+                hall: None,
+                laser: None,
+            },
+            hall: !self.use_hall,
+            laser: !self.use_laser,
+        };
+        self.pending.insert(trigger.seq, pending);
+    }
+
+    /// 记录一个采样，传感器任务按序号顺序处理，序号更早仍在等待该传感器的角度不会再有数据
+    pub fn arrive(&mut self, sample: SensorSample) {
+        let (seq, sensor) = match &sample {
+            SensorSample::Hall { seq, .. } => (*seq, Sensor::Hall),
+            SensorSample::Laser { seq, .. } => (*seq, Sensor::Laser),
+            SensorSample::Missing { seq, sensor, .. } => (*seq, *sensor),
+        };
+        for (&s, p) in self.pending.range_mut(..seq) {
+            let done = match sensor {
+                Sensor::Hall => &mut p.hall,
+                Sensor::Laser => &mut p.laser,
+            };
+            if !*done {
+                debug!("Angle={} (seq {}) {:?} sample skipped", p.record.angle, s, sensor);
+                *done = true;
+            }
+        }
+        let Some(p) = self.pending.get_mut(&seq) else { return };
+        match sample {
+            SensorSample::Hall { stat, .. } => {
+                p.record.hall = Some(stat);
+                p.hall = true;
+            }
+            SensorSample::Laser { points, .. } => {
+                p.record.laser = Some(points);
+                p.laser = true;
+            }
+            SensorSample::Missing { sensor: Sensor::Hall, .. } => p.hall = true,
+            SensorSample::Missing { sensor: Sensor::Laser, .. } => p.laser = true,
+        }
+    }
+
+    /// 取出已经到齐的角度，超出等待窗口的角度不再等待
+    pub fn take_ready(&mut self, latest: u64) -> Vec<AngleRecord> {
+        let mut ready = Vec::new();
+        while let Some(entry) = self.pending.first_entry() {
+            let p = entry.get();
+            if !(p.hall && p.laser) {
+                if *entry.key() + PENDING_WINDOW > latest {
+                    break;
+                }
+                warn!("Angle={} incomplete, hall={} laser={}", p.record.angle, p.hall, p.laser);
+            }
+            ready.push(entry.remove().record);
+        }
+        ready
+    }
+
+    /// 采集结束时取出所有角度，包括未到齐的
+    pub fn drain(&mut self) -> Vec<AngleRecord> {
+        let pending = std::mem::take(&mut self.pending);
+        pending
+            .into_values()
+            .map(|p| {
+                if !(p.hall && p.laser) {
+                    warn!("Angle={} incomplete, hall={} laser={}", p.record.angle, p.hall, p.laser);
+                }
+                p.record
+            })
+            .collect()
+    }
+}
+
+/// 采样的落盘与推送，与数据来源无关
+pub struct ScanSink {
+    app: Arc<AppWrapper>,
+    parent_id: i64,
+    max_std: Option<f32>,
+    laser_file: Option<File>,
+    hall_file: Option<File>,
+    v_file: Option<File>,
+    throttle: Throttle,
+    progress: Progress,
+    /// 整个检测期间复用的数据库连接
+    db: Connection,
+    /// 开始检测时的刀具类型，结束时按其磨损限值判定
+    cutter_type: String,
+    wear: WearAccumulator,
+}
+
+async fn open_append(path: &str) -> AppResult<File> {
+    OpenOptions::new()
+        .create(true) // 文件不存在就创建
+        .append(true) // 追加而不是覆盖
+        .open(path)
+        .await
+        .map_err(|e| AppError::io(Device::File, format!("无法打开{}: {}", path, e)))
+}
+
+async fn write_line(file: &mut Option<File>, line: &str) -> AppResult<()> {
+    match file {
+        Some(f) => f
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| AppError::io(Device::File, format!("写入失败: {}", e))),
+        None => Ok(()),
+    }
+}
+
+impl ScanSink {
+    pub async fn open(app: Arc<AppWrapper>, config: &ScanConfig) -> AppResult<Self> {
+        let cutter_type = app.settings.lock().await.cutter_type.clone();
+        let mut sink = ScanSink {
+            throttle: Throttle::new(app.app_handler.clone()),
+            progress: Progress::new(config.parent_id),
+            cutter_type,
+            wear: WearAccumulator::default(),
+            db: connect_to_db()?,
+            app,
+            parent_id: config.parent_id,
+            max_std: config.max_std,
+            laser_file: None,
+            hall_file: None,
+            v_file: None,
+        };
+        if let Some((hall_path, v_path)) = &config.hall_paths {
+            sink.hall_file = Some(open_append(hall_path).await?);
+            sink.v_file = Some(open_append(v_path).await?);
+        }
+        if let Some(laser_path) = &config.laser_path {
+            sink.laser_file = Some(open_append(laser_path).await?);
+        }
+        Ok(sink)
+    }
+
+    /// 电机到达新角度时推送位置和进度
+    pub fn on_angle(&mut self, angle: f32) {
+        let progress = self.progress.update(angle);
+        self.throttle.emit("motor_position", MotorPositionEvent { angle });
+        self.throttle.emit("session_progress", progress);
+    }
+
+    /// 采集结束时补发暂存的事件并推送最终进度
+    pub fn finish(&mut self) {
+        self.throttle.flush();
+        self.throttle.emit_now("session_progress", self.progress.event(true));
+    }
+
+    /// 按刀具的磨损限值给出检测结论，保存到项目并推送消息，没有设置限值时跳过
+    pub async fn judge(&mut self) {
+        let limits = self.app.settings.lock().await.wear_limits.get(&self.cutter_type).cloned();
+        let limits = match limits {
+            Some(l) if !l.is_empty() => l,
+            _ => {
+                info!("No wear limits for {}, verdict skipped", self.cutter_type);
+                return;
+            }
+        };
+        let verdict = Verdict::evaluate(self.cutter_type.clone(), self.wear.metrics(limits.nominal_radius), limits);
+        info!("Verdict for project {}: {}", self.parent_id, verdict.summary());
+        if let Err(e) = set_project_verdict(self.parent_id, &verdict) {
+            error!("Error saving verdict: {}", e);
+        }
+        match verdict.result {
+            VerdictResult::Pass => self.app.notify("success", "检测合格", verdict.summary()),
+            VerdictResult::Fail => self.app.notify("error", "检测不合格", verdict.summary()),
+            VerdictResult::Undetermined => self.app.notify("warning", "检测未判定", verdict.summary()),
+        }
+    }
+
+    /// 写入一个角度的数据，同一角度的霍尔和激光数据使用相同的角度和时间
+    pub async fn on_record(&mut self, record: AngleRecord) -> AppResult<()> {
+        debug!("Angle={}", record.angle);
+        if let Some(stat) = record.hall {
+            self.on_hall(record.angle, record.time, stat).await?;
+        }
+        if let Some(points) = record.laser {
+            self.on_laser(record.angle, record.time, points).await?;
+        }
+        Ok(())
+    }
+
+    /// 标准差超出阈值的角度只保存统计结果，不写入数据、文件和缓存
+    async fn on_hall(&mut self, angle: f32, time: DateTime<Local>, stat: HallStat) -> AppResult<()> {
+        if is_noisy(&stat, self.max_std) {
+            warn!("Angle={} rejected, std={}", angle, stat.max_std());
+            self.app.notify(
+                "warning",
+                "霍尔数据噪声过大",
+                format!("角度{}的标准差{:.2}超出阈值，已跳过", angle, stat.max_std()),
+            );
+            return insert_data_stat(&mut self.db, self.parent_id, angle, &stat, true);
+        }
+        let data = stat.rounded_mean();
+        insert_data_stat(&mut self.db, self.parent_id, angle, &stat, false)?;
+        insert_data(&self.db, self.parent_id, angle, time.timestamp_millis(), &data)?;
+        let v_array = hall_to_volts(&data);
+        self.wear.add_hall(&v_array);
+        let v_line = format!("{} {} {} {} {} {} {} {} {} {}\n",
+                             angle,
+                             v_array[0],
+                             v_array[1],
+                             v_array[2],
+                             v_array[3],
+                             v_array[4],
+                             v_array[5],
+                             v_array[6],
+                             v_array[7],
+                             v_array[8],
+        );
+        write_line(&mut self.v_file, &v_line).await?;
+        let line = format!(" {} {} {} {} {} {} {} {} {} {}\n",
+                           angle,
+                           data[0],
+                           data[1],
+                           data[2],
+                           data[3],
+                           data[4],
+                           data[5],
+                           data[6],
+                           data[7],
+                           data[8],
+        );
+        write_line(&mut self.hall_file, &line).await?;
+        self.throttle.emit_batch(
+            "hall_recv",
+            HallSampleEvent {
+                angle,
+                data: data.clone(),
+                volts: v_array,
+                std: stat.std.clone(),
+            },
+        );
+        self.app.push_hall_data(Payload { angle, data, std: stat.std }).await;
+        Ok(())
+    }
+
+    async fn on_laser(&mut self, angle: f32, time: DateTime<Local>, points: Vec<LaserData>) -> AppResult<()> {
+        self.throttle.emit("laser_recv", LaserSummaryEvent::new(angle, &points));
+        self.wear.add_laser(&points);
+        for datum in &points {
+            let line = format!("{} {} {}\n", datum.x, datum.y, datum.z);
+            write_line(&mut self.laser_file, &line).await?;
+        }
+        // 只推送序号，界面需要绘制时再取完整轮廓；按批发送，每个序号都能送到界面
+        let seq = self.app.push_laser_profile(angle, time.timestamp_millis(), points).await;
+        self.throttle.emit_batch("laser_profile", LaserProfileEvent { seq, angle });
+        Ok(())
+    }
+}
+
+fn is_noisy(stat: &HallStat, max_std: Option<f32>) -> bool {
+    max_std.is_some_and(|limit| stat.max_std() > limit)
+}
+
+/// 采集一个角度的霍尔数据，噪声超限时重新采样，仍超限时返回最后一次的结果
+async fn sample_hall(app: &AppWrapper, samples: u32, max_std: Option<f32>) -> AppResult<HallStat> {
+    let mut stat = app.get_hall_data_averaged(samples).await?;
+    for retry in 1..=HALL_RESAMPLE_RETRIES {
+        if !is_noisy(&stat, max_std) {
+            break;
+        }
+        debug!("Hall std {} over limit, resampling ({})", stat.max_std(), retry);
+        stat = app.get_hall_data_averaged(samples).await?;
+    }
+    Ok(stat)
+}
+
+/// 霍尔采集任务：每收到一个角度触发就采集一次
+fn spawn_hall_task(
+    app: Arc<AppWrapper>,
+    mut trigger_rx: broadcast::Receiver<AngleTrigger>,
+    sample_tx: mpsc::Sender<SensorSample>,
+    samples: u32,
+    max_std: Option<f32>,
+) {
+    tokio::spawn(async move {
+        loop {
+            let trigger = match trigger_rx.recv().await {
+                Ok(t) => t,
+                Err(RecvError::Lagged(n)) => {
+                    warn!("Hall task lagged, skipped {} angles", n);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let sample = match sample_hall(&app, samples, max_std).await {
+                Ok(stat) => SensorSample::Hall { seq: trigger.seq, stat },
+                Err(e) => SensorSample::Missing {
+                    seq: trigger.seq,
+                    sensor: Sensor::Hall,
+                    error: e,
+                },
+            };
+            if sample_tx.send(sample).await.is_err() {
+                break;
+            }
+        }
+        debug!("Hall task stopped");
+    });
+}
+
+/// 激光采集任务：激光较慢时会跳过积压的角度，不阻塞霍尔采样
+fn spawn_laser_task(
+    app: Arc<AppWrapper>,
+    mut trigger_rx: broadcast::Receiver<AngleTrigger>,
+    sample_tx: mpsc::Sender<SensorSample>,
+    laser_d: f32,
+) {
+    tokio::spawn(async move {
+        loop {
+            let trigger = match trigger_rx.recv().await {
+                Ok(t) => t,
+                Err(RecvError::Lagged(n)) => {
+                    warn!("Laser task lagged, skipped {} angles", n);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let sample = match app.get_laser_data().await {
+                Ok(frames) => SensorSample::Laser {
+                    seq: trigger.seq,
+                    points: laser_parse_data(frames, trigger.angle, laser_d).unwrap_or_default(),
+                },
+                Err(e) => SensorSample::Missing {
+                    seq: trigger.seq,
+                    sensor: Sensor::Laser,
+                    error: e,
+                },
+            };
+            if sample_tx.send(sample).await.is_err() {
+                break;
+            }
+        }
+        debug!("Laser task stopped");
+    });
+}
+
+pub async fn write_records(sink: &mut ScanSink, records: Vec<AngleRecord>) -> AppResult<()> {
+    for record in records {
+        sink.on_record(record).await?;
+    }
+    Ok(())
+}
+
+/// 启动一次采集：各传感器独立任务采样，同步器按序号把采样与电机角度合并为一条记录后写入
+pub fn spawn_scan(
+    app: Arc<AppWrapper>,
+    config: ScanConfig,
+    sink: ScanSink,
+    motor_rx: mpsc::Receiver<AppResult<f32>>,
+    stop_rx: watch::Receiver<bool>,
+) {
+    tokio::spawn(async move {
+        let (trigger_tx, _) = broadcast::channel::<AngleTrigger>(CHANNEL_SIZE);
+        let (sample_tx, mut sample_rx) = mpsc::channel::<SensorSample>(CHANNEL_SIZE);
+        // 只为启用的传感器启动采集任务
+        if config.use_hall() {
+            spawn_hall_task(app.clone(), trigger_tx.subscribe(), sample_tx.clone(), config.samples, config.max_std);
+        }
+        if config.use_laser() {
+            spawn_laser_task(app.clone(), trigger_tx.subscribe(), sample_tx.clone(), config.laser_d);
+        }
+        drop(sample_tx);
+
+        let mut sink = sink;
+        let mut motor_rx = motor_rx;
+        let mut stop_rx = stop_rx; // mutable
+        let mut trigger_tx = Some(trigger_tx);
+        let mut joiner = Joiner::new(config.use_hall(), config.use_laser());
+        let mut seq: u64 = 0;
+        // 第一个导致采集中止的错误，之后到达的采样不再写入
+        let mut failure: Option<AppError> = None;
+        let abort = |title: &str, e: AppError, failure: &mut Option<AppError>| {
+            error!("Scan aborted: {}", e);
+            app.notify("error", title, format!("采集已中止: {}", e));
+            let _ = app.stop_tx.send(true);
+            if failure.is_none() {
+                *failure = Some(e);
+            }
+        };
+        loop {
+            tokio::select! {
+                angle = motor_rx.recv(), if trigger_tx.is_some() => {
+                    match angle {
+                        Some(Ok(a)) => {
+                            seq += 1;
+                            sink.on_angle(a);
+                            let trigger = AngleTrigger { seq, angle: a, time: Local::now() };
+                            joiner.insert(&trigger);
+                            if let Some(tx) = &trigger_tx {
+                                let _ = tx.send(trigger);
+                            }
+                        }
+                        Some(Err(e)) => {
+                            abort("电机控制器异常", e, &mut failure);
+                            trigger_tx = None;
+                        }
+                        None => trigger_tx = None, // channel 关闭
+                    }
+                }
+
+                sample = sample_rx.recv() => {
+                    let Some(sample) = sample else {
+                        break; // 所有传感器任务已退出
+                    };
+                    if let SensorSample::Missing { sensor, error, .. } = &sample {
+                        match sensor {
+                            Sensor::Hall => {
+                                // 霍尔数据是检测的主体，失败时结束本次采集
+                                error!("Error getting hall data: {}", error);
+                                abort("霍尔传感器异常", error.clone(), &mut failure);
+                                trigger_tx = None;
+                            }
+                            Sensor::Laser => {
+                                // 激光偶发失败只记录并跳过该角度
+                                warn!("Error getting laser data: {}", error);
+                            }
+                        }
+                    }
+                    joiner.arrive(sample);
+                }
+
+                _ = stop_rx.changed(), if trigger_tx.is_some() => {
+                    if *stop_rx.borrow() {
+                        info!("Motor listener stopping...");
+                        // 关闭触发 channel，传感器任务处理完手头的角度后退出
+                        trigger_tx = None;
+                    }
+                }
+            }
+            let ready = joiner.take_ready(seq);
+            if failure.is_none() {
+                if let Err(e) = write_records(&mut sink, ready).await {
+                    abort("数据保存失败", e, &mut failure);
+                    trigger_tx = None;
+                }
+            }
+        }
+        if failure.is_none() {
+            if let Err(e) = write_records(&mut sink, joiner.drain()).await {
+                abort("数据保存失败", e, &mut failure);
+            }
+        }
+        let status = if failure.is_some() { PROJECT_ABORTED } else { PROJECT_FINISHED };
+        if let Err(e) = set_project_status(config.parent_id, status) {
+            error!("Error updating project status: {}", e);
+        }
+        if failure.is_none() {
+            sink.judge().await;
+        }
+        sink.finish();
+        if let Some(path) = &config.capture {
+            app.capture.stop_file(path);
+        }
+        app.scanning.store(false, Ordering::SeqCst);
+        info!("Scan stopped");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial::hall_statistics;
+
+    fn trigger(seq: u64) -> AngleTrigger {
+        AngleTrigger {
+            seq,
+            angle: seq as f32,
+            time: Local::now(),
+        }
+    }
+
+    fn hall(seq: u64) -> SensorSample {
+        SensorSample::Hall {
+            seq,
+            stat: hall_statistics(&[vec![seq as i32]]).unwrap(),
+        }
+    }
+
+    fn laser(seq: u64) -> SensorSample {
+        SensorSample::Laser {
+            seq,
+            points: vec![LaserData { x: 1.0, y: 0.0, z: 0.0 }],
+        }
+    }
+
+    #[test]
+    fn joins_both_sensors_into_one_record() {
+        let mut joiner = Joiner::new(true, true);
+        joiner.insert(&trigger(1));
+        joiner.arrive(laser(1));
+        assert!(joiner.take_ready(1).is_empty());
+        joiner.arrive(hall(1));
+        let ready = joiner.take_ready(1);
+        assert_eq!(ready.len(), 1);
+        assert!(ready[0].hall.is_some() && ready[0].laser.is_some());
+    }
+
+    #[test]
+    fn releases_records_in_seq_order() {
+        let mut joiner = Joiner::new(true, false);
+        joiner.insert(&trigger(1));
+        joiner.insert(&trigger(2));
+        // 第 2 个角度先到齐，也要等第 1 个角度
+        joiner.pending.get_mut(&2).unwrap().hall = true;
+        assert!(joiner.take_ready(2).is_empty());
+        joiner.arrive(hall(1));
+        let angles: Vec<f32> = joiner.take_ready(2).iter().map(|r| r.angle).collect();
+        assert_eq!(angles, vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn later_sample_marks_skipped_angles_missing() {
+        let mut joiner = Joiner::new(true, true);
+        for seq in 1..=3 {
+            joiner.insert(&trigger(seq));
+            joiner.arrive(hall(seq));
+        }
+        // 激光任务跳过了前两个角度
+        joiner.arrive(laser(3));
+        let ready = joiner.take_ready(3);
+        assert_eq!(ready.len(), 3);
+        assert!(ready[0].laser.is_none() && ready[1].laser.is_none());
+        assert!(ready[2].laser.is_some());
+    }
+
+    #[test]
+    fn missing_sample_completes_angle() {
+        let mut joiner = Joiner::new(true, true);
+        joiner.insert(&trigger(1));
+        joiner.arrive(hall(1));
+        joiner.arrive(SensorSample::Missing {
+            seq: 1,
+            sensor: Sensor::Laser,
+            error: AppError::Timeout(Device::Laser),
+        });
+        let ready = joiner.take_ready(1);
+        assert_eq!(ready.len(), 1);
+        assert!(ready[0].laser.is_none());
+    }
+
+    #[test]
+    fn window_overflow_and_drain_release_incomplete_angles() {
+        let mut joiner = Joiner::new(true, true);
+        joiner.insert(&trigger(1));
+        assert!(joiner.take_ready(PENDING_WINDOW).is_empty());
+        assert_eq!(joiner.take_ready(PENDING_WINDOW + 1).len(), 1);
+        joiner.insert(&trigger(2));
+        assert_eq!(joiner.drain().len(), 1);
+        assert!(joiner.pending.is_empty());
+    }
+}
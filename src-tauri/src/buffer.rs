@@ -0,0 +1,183 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+/// 带序号的缓冲区元素，序号从 1 开始单调递增
+#[derive(Clone, Serialize)]
+pub struct Sequenced<T> {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub item: T,
+}
+
+/// 一次按序号读取的结果
+#[derive(Clone, Serialize)]
+pub struct RingSlice<T> {
+    pub items: Vec<Sequenced<T>>,
+    /// 下次读取时传入的序号
+    pub next_seq: u64,
+    /// 请求的数据已被覆盖的条数，没有丢失时为 0
+    pub lost: u64,
+    /// 传入的序号超出已分配的序号（如后端重启后序号重新计数）时为真，此时从最旧的数据开始返回
+    pub reset: bool,
+}
+
+/// 定长环形缓冲区，读取不会移除数据，多个读者各自记录读到的序号
+pub struct SeqRing<T> {
+    items: VecDeque<Sequenced<T>>,
+    capacity: usize,
+    next_seq: u64,
+    cursors: HashMap<String, u64>,
+}
+
+impl<T: Clone> SeqRing<T> {
+    pub fn new(capacity: usize) -> Self {
+        SeqRing {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+            next_seq: 1,
+            cursors: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, item: T) -> u64 {
+        if self.items.len() >= self.capacity {
+            self.items.pop_front(); // 丢弃最旧的
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.items.push_back(Sequenced { seq, item });
+        seq
+    }
+
+    fn oldest_seq(&self) -> u64 {
+        self.items.front().map(|s| s.seq).unwrap_or(self.next_seq)
+    }
+
+    /// 读取序号不小于 since 的数据，最多 max 条，since 为 0 时从最旧的数据开始
+    pub fn since(&self, since: u64, max: usize) -> RingSlice<T> {
+        let oldest = self.oldest_seq();
+        let reset = since > self.next_seq;
+        let since = if reset { 0 } else { since };
+        let lost = if since == 0 { 0 } else { oldest.saturating_sub(since) };
+        let start = since.max(oldest);
+        let skip = (start - oldest) as usize;
+        let items: Vec<Sequenced<T>> = self.items.iter().skip(skip).take(max).cloned().collect();
+        let next_seq = items.last().map(|s| s.seq + 1).unwrap_or(start);
+        RingSlice {
+            items,
+            next_seq,
+            lost,
+            reset,
+        }
+    }
+
+    /// 按读者记录的序号继续读取并前移，新读者从当前最旧的数据开始，序号失效时同 since 一样重新开始
+    pub fn read(&mut self, consumer: &str, max: usize) -> RingSlice<T> {
+        let since = self.cursors.get(consumer).copied().unwrap_or_else(|| self.oldest_seq());
+        let slice = self.since(since, max);
+        self.cursors.insert(consumer.to_string(), slice.next_seq);
+        slice
+    }
+
+    /// 按序号取数据，已被覆盖或尚未写入时为空
+    pub fn get(&self, seq: u64) -> Option<Sequenced<T>> {
+        let index = seq.checked_sub(self.oldest_seq())?;
+        self.items.get(index as usize).cloned()
+    }
+
+    pub fn latest(&self) -> Option<Sequenced<T>> {
+        self.items.back().cloned()
+    }
+
+    /// 从最新数据向前遍历
+    pub fn iter_latest(&self) -> impl Iterator<Item = &Sequenced<T>> {
+        self.items.iter().rev()
+    }
+
+    /// 从最新数据向前取，直到 take 返回 false
+    pub fn latest_while(&self, mut take: impl FnMut(&T) -> bool) -> Vec<Sequenced<T>> {
+        let mut items: Vec<Sequenced<T>> = Vec::new();
+        for s in self.items.iter().rev() {
+            if !take(&s.item) {
+                break;
+            }
+            items.push(s.clone());
+        }
+        items.reverse();
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(capacity: usize, count: u32) -> SeqRing<u32> {
+        let mut ring = SeqRing::new(capacity);
+        for i in 0..count {
+            ring.push(i);
+        }
+        ring
+    }
+
+    fn seqs(slice: &RingSlice<u32>) -> Vec<u64> {
+        slice.items.iter().map(|s| s.seq).collect()
+    }
+
+    #[test]
+    fn since_reads_in_order() {
+        let ring = ring(8, 5);
+        let slice = ring.since(2, 2);
+        assert_eq!(seqs(&slice), vec![2, 3]);
+        assert_eq!(slice.next_seq, 4);
+        assert_eq!(slice.lost, 0);
+        assert!(!slice.reset);
+        // 已读到最新时返回空，序号不变
+        let slice = ring.since(6, 10);
+        assert!(slice.items.is_empty());
+        assert_eq!(slice.next_seq, 6);
+    }
+
+    #[test]
+    fn overflow_reports_lost_items() {
+        let ring = ring(4, 10);
+        let slice = ring.since(3, 10);
+        assert_eq!(seqs(&slice), vec![7, 8, 9, 10]);
+        assert_eq!(slice.lost, 4);
+        assert_eq!(ring.since(0, 10).lost, 0);
+    }
+
+    #[test]
+    fn get_by_seq() {
+        let ring = ring(4, 6);
+        assert_eq!(ring.get(5).map(|s| s.item), Some(4));
+        assert!(ring.get(2).is_none());
+        assert!(ring.get(7).is_none());
+    }
+
+    #[test]
+    fn seq_beyond_next_is_reset() {
+        let ring = ring(4, 3);
+        let slice = ring.since(100, 10);
+        assert!(slice.reset);
+        assert_eq!(seqs(&slice), vec![1, 2, 3]);
+        assert_eq!(slice.next_seq, 4);
+        assert_eq!(slice.lost, 0);
+    }
+
+    #[test]
+    fn cursors_are_independent() {
+        let mut ring = ring(4, 3);
+        assert_eq!(seqs(&ring.read("a", 2)), vec![1, 2]);
+        assert_eq!(seqs(&ring.read("b", 10)), vec![1, 2, 3]);
+        ring.push(3);
+        assert_eq!(seqs(&ring.read("a", 10)), vec![3, 4]);
+        for i in 0..6 {
+            ring.push(i);
+        }
+        let slice = ring.read("b", 10);
+        assert_eq!(seqs(&slice), vec![7, 8, 9, 10]);
+        assert_eq!(slice.lost, 3);
+        assert!(ring.read("b", 10).items.is_empty());
+    }
+}
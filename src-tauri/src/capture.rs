@@ -0,0 +1,310 @@
+use crate::error::{AppError, AppResult, Device};
+use crate::motor::parse_frame;
+use crate::serial::hall_parse_data;
+use chrono::{DateTime, Local};
+use log::{error, info, warn};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+/// 抓包文件头，后跟若干条记录
+const MAGIC: &[u8; 6] = b"ADCAP\x01";
+/// 每条记录：8 字节微秒时间戳、1 字节设备、1 字节方向、4 字节长度，均为小端，后跟原始数据
+const RECORD_HEADER: usize = 14;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// 上位机发给设备
+    Tx,
+    /// 设备发给上位机
+    Rx,
+}
+
+fn device_code(device: Device) -> u8 {
+    match device {
+        Device::Hall => 0,
+        Device::Motor => 1,
+        Device::Laser => 2,
+        Device::System => 3,
+        _ => 255,
+    }
+}
+
+fn device_from_code(code: u8) -> Option<Device> {
+    match code {
+        0 => Some(Device::Hall),
+        1 => Some(Device::Motor),
+        2 => Some(Device::Laser),
+        3 => Some(Device::System),
+        _ => None,
+    }
+}
+
+struct CaptureFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+/// 设备收发数据记录器，未开启时 record 只做一次原子读取
+#[derive(Default)]
+pub struct Capture {
+    enabled: AtomicBool,
+    file: Mutex<Option<CaptureFile>>,
+}
+
+impl Capture {
+    /// 开始记录到新文件，已在记录时先结束上一个文件
+    pub fn start(&self, path: PathBuf) -> AppResult<()> {
+        self.begin(path, true).map(|_| ())
+    }
+
+    /// 没有在记录时才开始记录，已在记录时保留原来的文件，返回是否开始了新的记录
+    pub fn start_if_idle(&self, path: PathBuf) -> AppResult<bool> {
+        self.begin(path, false)
+    }
+
+    fn begin(&self, path: PathBuf, replace: bool) -> AppResult<bool> {
+        let mut lock = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if lock.is_some() && !replace {
+            return Ok(false);
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| AppError::io(Device::File, e))?;
+        }
+        let file = File::create(&path)
+            .map_err(|e| AppError::io(Device::File, format!("无法创建{}: {}", path.display(), e)))?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(MAGIC).map_err(|e| AppError::io(Device::File, e))?;
+        if let Some(old) = lock.take() {
+            finish(old);
+        }
+        info!("Capture started: {}", path.display());
+        *lock = Some(CaptureFile { path, writer });
+        self.enabled.store(true, Ordering::SeqCst);
+        Ok(true)
+    }
+
+    /// 结束记录，返回抓包文件路径
+    pub fn stop(&self) -> Option<PathBuf> {
+        self.enabled.store(false, Ordering::SeqCst);
+        let file = self.file.lock().unwrap_or_else(|e| e.into_inner()).take()?;
+        Some(finish(file))
+    }
+
+    /// 只在正在记录的是 path 时结束记录，不会结束之后手动开始的记录
+    pub fn stop_file(&self, path: &Path) -> Option<PathBuf> {
+        let mut lock = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if lock.as_ref().map(|f| f.path.as_path()) != Some(path) {
+            return None;
+        }
+        self.enabled.store(false, Ordering::SeqCst);
+        lock.take().map(finish)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn record(&self, device: Device, direction: Direction, data: &[u8]) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut lock = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(file) = lock.as_mut() else { return };
+        let mut header = [0u8; RECORD_HEADER];
+        header[0..8].copy_from_slice(&Local::now().timestamp_micros().to_le_bytes());
+        header[8] = device_code(device);
+        header[9] = direction as u8;
+        header[10..14].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        let res = file.writer.write_all(&header).and_then(|_| file.writer.write_all(data));
+        if let Err(e) = res {
+            // 写入失败时停止记录，避免每一帧都报错
+            error!("Capture write failed, stopping: {}", e);
+            self.enabled.store(false, Ordering::SeqCst);
+            if let Some(file) = lock.take() {
+                finish(file);
+            }
+        }
+    }
+}
+
+fn finish(mut file: CaptureFile) -> PathBuf {
+    if let Err(e) = file.writer.flush() {
+        warn!("Capture flush failed: {}", e);
+    }
+    info!("Capture stopped: {}", file.path.display());
+    file.path
+}
+
+/// 抓包文件默认目录
+pub fn capture_dir(app: &AppHandle) -> AppResult<PathBuf> {
+    let mut path = app.path().app_log_dir().map_err(|e| AppError::io(Device::File, e))?;
+    path.push("capture");
+    Ok(path)
+}
+
+/// 按当前时间生成抓包文件名，tag 用于区分检测项目
+pub fn capture_file_name(tag: &str) -> String {
+    format!("{}-{}.adcap", tag, Local::now().format("%Y%m%d-%H%M%S"))
+}
+
+/// 解码后的一条记录
+#[derive(Clone, Serialize)]
+pub struct CaptureRecord {
+    pub time: String,
+    pub device: Device,
+    pub direction: Direction,
+    pub len: usize,
+    pub hex: String,
+    /// 按设备协议解析的内容，无法解析时为空
+    pub decoded: Option<String>,
+}
+
+fn decode_payload(device: Device, direction: Direction, data: &[u8]) -> Option<String> {
+    match (device, direction) {
+        (Device::Hall, Direction::Tx) => (data == [0xFF, 0xEE, 0xAA, 0xEF, 0xFE]).then(|| "读取霍尔数据".to_string()),
+        (Device::Hall, Direction::Rx) => {
+            let values = hall_parse_data(data)?;
+            Some(format!("霍尔数据 {:?}", values))
+        }
+        (Device::Motor, _) => {
+            let value = parse_frame(data).ok()?;
+            Some(format!("命令{} 数值{} (浮点{})", data[2], value, f32::from_bits(value)))
+        }
+        (Device::Laser, Direction::Tx) => {
+            (data == [0xAA, 0x55, 0x55, 0xAA, 0x02, 0x00, 0x21, 0x01]).then(|| "读取激光轮廓".to_string())
+        }
+        (Device::Laser, Direction::Rx) => {
+            let frame_id = data.last()?;
+            Some(format!("轮廓帧{}，{}字节", frame_id, data.len() - 1))
+        }
+        // 探测串口时还不知道是哪种设备，依次按电机和霍尔的协议解码
+        (Device::System, _) => decode_payload(Device::Motor, direction, data)
+            .or_else(|| decode_payload(Device::Hall, direction, data))
+            .map(|s| format!("探测: {}", s)),
+        _ => None,
+    }
+}
+
+/// 读取抓包文件并按协议解码
+pub fn decode_file(path: &Path) -> AppResult<Vec<CaptureRecord>> {
+    let bytes = std::fs::read(path).map_err(|e| AppError::io(Device::File, format!("无法读取{}: {}", path.display(), e)))?;
+    if !bytes.starts_with(MAGIC) {
+        return Err(AppError::parse(Device::File, "不是抓包文件"));
+    }
+    let mut records = Vec::new();
+    let mut pos = MAGIC.len();
+    while pos < bytes.len() {
+        // 记录被截断时保留已解析的部分
+        if bytes.len() - pos < RECORD_HEADER {
+            warn!("Capture truncated at {}", pos);
+            break;
+        }
+        let header = &bytes[pos..pos + RECORD_HEADER];
+        let micros = i64::from_le_bytes(header[0..8].try_into().unwrap_or_default());
+        let len = u32::from_le_bytes(header[10..14].try_into().unwrap_or_default()) as usize;
+        pos += RECORD_HEADER;
+        if bytes.len() - pos < len {
+            warn!("Capture truncated at {}", pos);
+            break;
+        }
+        let data = &bytes[pos..pos + len];
+        pos += len;
+        let Some(device) = device_from_code(header[8]) else { continue };
+        let direction = if header[9] == Direction::Tx as u8 { Direction::Tx } else { Direction::Rx };
+        let time = DateTime::from_timestamp_micros(micros)
+            .map(|t| t.with_timezone(&Local).format("%H:%M:%S%.6f").to_string())
+            .unwrap_or_default();
+        records.push(CaptureRecord {
+            time,
+            device,
+            direction,
+            len,
+            hex: data.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" "),
+            decoded: decode_payload(device, direction, data),
+        });
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motor::encode_frame;
+    use std::fs::OpenOptions;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("adcap-{}-{}.adcap", name, std::process::id()))
+    }
+
+    fn write_sample(path: &Path) {
+        let capture = Capture::default();
+        // 未开始时不记录
+        capture.record(Device::Motor, Direction::Tx, &[0x00]);
+        capture.start(path.to_path_buf()).unwrap();
+        capture.record(Device::Motor, Direction::Tx, &encode_frame(4, 0));
+        capture.record(Device::Hall, Direction::Tx, &[0xFF, 0xEE, 0xAA, 0xEF, 0xFE]);
+        capture.record(Device::System, Direction::Rx, &encode_frame(4, 90f32.to_bits()));
+        assert_eq!(capture.stop().as_deref(), Some(path));
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_path("round-trip");
+        write_sample(&path);
+        let records = decode_file(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].device, Device::Motor);
+        assert_eq!(records[0].direction, Direction::Tx);
+        assert_eq!(records[0].len, 9);
+        assert_eq!(records[0].hex, "EF FE 04 00 00 00 00 FF EE");
+        assert_eq!(records[0].decoded.as_deref(), Some("命令4 数值0 (浮点0)"));
+        assert_eq!(records[1].decoded.as_deref(), Some("读取霍尔数据"));
+        assert_eq!(records[2].device, Device::System);
+        assert!(records[2].decoded.as_deref().is_some_and(|d| d.starts_with("探测: 命令4")));
+    }
+
+    #[test]
+    fn session_capture_keeps_manual_capture() {
+        let manual = temp_path("manual");
+        let session = temp_path("session");
+        let capture = Capture::default();
+        capture.start(manual.clone()).unwrap();
+        // 手动记录进行中时不开始检测的记录，也不会被检测结束时关闭
+        assert!(!capture.start_if_idle(session.clone()).unwrap());
+        assert!(!session.exists());
+        assert!(capture.stop_file(&session).is_none());
+        assert!(capture.is_enabled());
+        assert_eq!(capture.stop().as_deref(), Some(manual.as_path()));
+        assert!(capture.start_if_idle(session.clone()).unwrap());
+        assert_eq!(capture.stop_file(&session).as_deref(), Some(session.as_path()));
+        assert!(!capture.is_enabled());
+        let _ = std::fs::remove_file(&manual);
+        let _ = std::fs::remove_file(&session);
+    }
+
+    #[test]
+    fn truncated_file_keeps_complete_records() {
+        let path = temp_path("truncated");
+        write_sample(&path);
+        let full = std::fs::read(&path).unwrap();
+        // 最后一条记录只写了一半
+        std::fs::write(&path, &full[..full.len() - 4]).unwrap();
+        assert_eq!(decode_file(&path).unwrap().len(), 2);
+        // 末尾只有半个记录头
+        std::fs::write(&path, &full).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0u8; RECORD_HEADER - 1]).unwrap();
+        drop(file);
+        assert_eq!(decode_file(&path).unwrap().len(), 3);
+        std::fs::write(&path, b"not a capture").unwrap();
+        assert!(decode_file(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,539 @@
+use crate::capture::{Capture, Direction};
+use crate::error::{AppError, AppResult, Device};
+use crate::settings::{SerialSettings, UsbId};
+use crate::motor::parse_frame;
+use crate::safety::read_interlock;
+use crate::serial::{hall_frame_valid, list_ports};
+use crate::{AppWrapper, PortInfo, SerialPortList};
+use futures::{SinkExt, StreamExt};
+use log::{info, warn};
+use serde::Serialize;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Instant};
+use tokio_serial::{SerialPortBuilderExt, SerialPortType};
+use tokio_util::bytes::{Bytes, BytesMut};
+use tokio_util::codec::{BytesCodec, Framed};
+
+/// 健康检查周期
+const CHECK_INTERVAL: Duration = Duration::from_secs(3);
+/// 连续失败多少次判定为断线
+const FAILURE_THRESHOLD: u32 = 2;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// 串口列表轮询周期
+const PORT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// 激光发现时等待应答的时长
+const DISCOVER_WINDOW: Duration = Duration::from_millis(1500);
+
+/// 串口设备的连接目标，记录 USB 设备标识以便端口号变化后找回设备
+#[derive(Clone, Debug)]
+pub struct SerialTarget {
+    pub port: String,
+    pub usb_id: Option<UsbId>,
+}
+
+impl SerialTarget {
+    pub fn lookup(port: &str) -> Self {
+        let usb_id = tokio_serial::available_ports().ok().and_then(|ports| {
+            ports
+                .into_iter()
+                .find(|p| p.port_name == port)
+                .and_then(|p| usb_id_of(&p.port_name, &p.port_type))
+        });
+        SerialTarget {
+            port: port.to_string(),
+            usb_id,
+        }
+    }
+
+    /// 优先使用原端口，原端口消失或换成了其他设备时按序列号查找重新枚举后的端口，
+    /// 没有序列号的设备只认原端口路径，避免打开同型号的其他适配器
+    pub fn resolve(&self) -> Option<String> {
+        let ports = tokio_serial::available_ports().ok()?;
+        let Some(id) = &self.usb_id else {
+            return ports.into_iter().find(|p| p.port_name == self.port).map(|p| p.port_name);
+        };
+        ports
+            .into_iter()
+            .filter(|p| matches!(&p.port_type, SerialPortType::UsbPort(info) if id.matches(&p.port_name, info)))
+            .min_by_key(|p| p.port_name != self.port)
+            .map(|p| p.port_name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStatus {
+    Connected,
+    Disconnected,
+    Reconnecting,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DeviceStatusPayload {
+    pub device: Device,
+    pub status: LinkStatus,
+    pub message: String,
+}
+
+/// 供前端查询的单个设备连接情况
+#[derive(Clone, Serialize)]
+pub struct DeviceState {
+    pub connected: bool,
+    /// 串口号或激光地址，未连接时为空
+    pub target: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DeviceStatusList {
+    pub hall: DeviceState,
+    pub motor: DeviceState,
+    pub laser: DeviceState,
+}
+
+pub async fn device_state(app: &AppWrapper, device: Device) -> DeviceState {
+    let target = match device {
+        Device::Hall => app.hall_target.lock().await.as_ref().map(|t| t.port.clone()),
+        Device::Motor => app.motor_target.lock().await.as_ref().map(|t| t.port.clone()),
+        Device::Laser => app.laser_address.lock().await.clone(),
+        _ => None,
+    };
+    DeviceState {
+        connected: is_open(app, device).await,
+        target,
+    }
+}
+
+/// 单个设备在监测任务中的状态
+struct Health {
+    device: Device,
+    status: LinkStatus,
+    failures: u32,
+    attempts: u32,
+    next_attempt: Instant,
+}
+
+impl Health {
+    fn new(device: Device) -> Self {
+        Health {
+            device,
+            status: LinkStatus::Disconnected,
+            failures: 0,
+            attempts: 0,
+            next_attempt: Instant::now(),
+        }
+    }
+
+    fn backoff(&self) -> Duration {
+        let factor = 1u32 << self.attempts.min(5);
+        (MIN_BACKOFF * factor).min(MAX_BACKOFF)
+    }
+}
+
+/// 超时、读写失败和串口关闭说明链路已断，其余错误说明设备仍有响应
+fn is_link_lost(e: &AppError) -> bool {
+    matches!(e, AppError::Timeout(_) | AppError::Io { .. } | AppError::Disconnected(_))
+}
+
+/// 占用设备进行采集或运动，等待正在进行的健康探测结束，已被占用时返回 false
+pub async fn claim(app: &AppWrapper, flag: &AtomicBool) -> bool {
+    let _probe = app.probe_lock.lock().await;
+    !flag.swap(true, Ordering::SeqCst)
+}
+
+async fn is_configured(app: &AppWrapper, device: Device) -> bool {
+    match device {
+        Device::Hall => app.hall_target.lock().await.is_some(),
+        Device::Motor => app.motor_target.lock().await.is_some(),
+        Device::Laser => app.laser_address.lock().await.is_some(),
+        _ => false,
+    }
+}
+
+pub async fn is_open(app: &AppWrapper, device: Device) -> bool {
+    match device {
+        Device::Hall => app.hall_serial.lock().await.is_some(),
+        Device::Motor => app.motor_link().await.is_ok(),
+        Device::Laser => app.laser_socket.lock().await.is_some(),
+        _ => false,
+    }
+}
+
+async fn ping(app: &AppWrapper, device: Device) -> AppResult<()> {
+    match device {
+        Device::Hall => app.get_hall_data().await.map(|_| ()),
+        Device::Motor => {
+            app.get_motor_angle().await?;
+            // 未开启扩展协议时只返回本地状态，读取失败不影响链路判断
+            if let Err(e) = read_interlock(app).await {
+                warn!("Failed to read interlock: {}", e);
+            }
+            Ok(())
+        }
+        Device::Laser => app.ping_laser().await,
+        _ => Ok(()),
+    }
+}
+
+async fn reconnect(app: &AppWrapper, device: Device) -> AppResult<()> {
+    // 先释放旧句柄，否则同一端口无法再次打开
+    app.close_device(device).await;
+    match device {
+        Device::Hall | Device::Motor => {
+            let target = match device {
+                Device::Hall => app.hall_target.lock().await.clone(),
+                _ => app.motor_target.lock().await.clone(),
+            };
+            let port = target
+                .and_then(|t| t.resolve())
+                .ok_or(AppError::Disconnected(device))?;
+            match device {
+                Device::Hall => app.connect_hall(&port, None).await,
+                _ => app.connect_motor(&port, None).await,
+            }
+        }
+        Device::Laser => {
+            let addr = app
+                .laser_address
+                .lock()
+                .await
+                .clone()
+                .ok_or(AppError::Disconnected(device))?;
+            app.connect_laser(addr, None).await
+        }
+        _ => Ok(()),
+    }
+}
+
+fn emit_status(app: &AppWrapper, h: &mut Health, status: LinkStatus, message: String) {
+    h.status = status;
+    let payload = DeviceStatusPayload {
+        device: h.device,
+        status,
+        message,
+    };
+    if let Err(e) = app.app_handler.emit("device_status", payload) {
+        warn!("Failed to emit device status: {}", e);
+    }
+}
+
+async fn check_device(app: &AppWrapper, h: &mut Health) {
+    if !is_configured(app, h.device).await {
+        // 用户主动断开或从未连接
+        if h.status != LinkStatus::Disconnected {
+            emit_status(app, h, LinkStatus::Disconnected, "已断开".into());
+        }
+        h.failures = 0;
+        h.attempts = 0;
+        return;
+    }
+    if is_open(app, h.device).await {
+        // 持有探测锁直到探测结束，期间不会开始新的采集
+        let _probe = app.probe_lock.lock().await;
+        if app.scanning.load(Ordering::SeqCst) || app.moving.load(Ordering::SeqCst) {
+            // 采集或运动过程中由对应任务占用设备，不插入探测命令
+            return;
+        }
+        match ping(app, h.device).await {
+            Err(e) if is_link_lost(&e) => {
+                h.failures += 1;
+                if h.failures >= FAILURE_THRESHOLD {
+                    warn!("{:?} link lost: {}", h.device, e);
+                    app.close_device(h.device).await;
+                    h.attempts = 0;
+                    h.next_attempt = Instant::now();
+                    emit_status(app, h, LinkStatus::Disconnected, e.to_string());
+                }
+            }
+            _ => {
+                h.failures = 0;
+                if h.status != LinkStatus::Connected {
+                    emit_status(app, h, LinkStatus::Connected, "已连接".into());
+                }
+            }
+        }
+        return;
+    }
+    if Instant::now() < h.next_attempt {
+        return;
+    }
+    emit_status(app, h, LinkStatus::Reconnecting, format!("第{}次重连", h.attempts + 1));
+    match reconnect(app, h.device).await {
+        Ok(_) => {
+            info!("{:?} reconnected", h.device);
+            h.failures = 0;
+            h.attempts = 0;
+            emit_status(app, h, LinkStatus::Connected, "重连成功".into());
+        }
+        Err(e) => {
+            h.attempts += 1;
+            h.next_attempt = Instant::now() + h.backoff();
+            emit_status(app, h, LinkStatus::Disconnected, e.to_string());
+        }
+    }
+}
+
+/// 周期性探测已连接的设备，断线后按退避间隔尝试重连，状态变化通过 device_status 事件通知前端
+pub fn spawn_health_monitor(app: Arc<AppWrapper>) {
+    tauri::async_runtime::spawn(async move {
+        let mut health = [
+            Health::new(Device::Hall),
+            Health::new(Device::Motor),
+            Health::new(Device::Laser),
+        ];
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            for h in health.iter_mut() {
+                check_device(&app, h).await;
+            }
+        }
+    });
+}
+
+/// 轮询串口列表，插拔设备导致列表变化时发送 ports_changed 事件
+pub fn spawn_port_monitor(app: Arc<AppWrapper>) {
+    tauri::async_runtime::spawn(async move {
+        let mut last: Option<Vec<PortInfo>> = None;
+        let mut ticker = tokio::time::interval(PORT_POLL_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let (hall_id, motor_id) = {
+                let settings = app.settings.lock().await;
+                (settings.hall_usb_id.clone(), settings.motor_usb_id.clone())
+            };
+            let ports = match list_ports(hall_id.as_ref(), motor_id.as_ref()) {
+                Ok(ports) => ports,
+                Err(e) => {
+                    warn!("{}", e);
+                    continue;
+                }
+            };
+            if last.as_ref() == Some(&ports) {
+                continue;
+            }
+            // 第一次只记录基线，不发送事件
+            if last.is_some() {
+                let payload = SerialPortList {
+                    port_vec: ports.clone(),
+                };
+                if let Err(e) = app.app_handler.emit("ports_changed", payload) {
+                    warn!("Failed to emit ports changed: {}", e);
+                }
+            }
+            last = Some(ports);
+        }
+    });
+}
+
+/// 单个串口的探测结果
+#[derive(Clone, Serialize)]
+pub struct ProbeResult {
+    pub port: String,
+    /// 应答的设备，无应答时为空
+    pub device: Option<Device>,
+    pub usb_id: Option<UsbId>,
+    /// 端口已被本程序占用，未重新探测
+    pub in_use: bool,
+    pub message: String,
+}
+
+fn usb_id_of(port: &str, port_type: &SerialPortType) -> Option<UsbId> {
+    match port_type {
+        SerialPortType::UsbPort(info) => Some(UsbId::of(port, info)),
+        _ => None,
+    }
+}
+
+/// 以给定参数打开串口，发送请求并累积应答，直到 accept 认可或超时，收发数据按 Device::System 记录
+async fn exchange(
+    capture: &Capture,
+    port: &str,
+    settings: &SerialSettings,
+    request: &[u8],
+    accept: impl Fn(&[u8]) -> bool,
+) -> AppResult<bool> {
+    let stream = settings
+        .builder(port)
+        .open_native_async()
+        .map_err(|e| AppError::io(Device::System, e))?;
+    let mut framed = Framed::new(stream, BytesCodec::new());
+    framed
+        .send(Bytes::copy_from_slice(request))
+        .await
+        .map_err(|e| AppError::io(Device::System, e))?;
+    capture.record(Device::System, Direction::Tx, request);
+    let deadline = Instant::now() + settings.timeout();
+    let mut buf = BytesMut::new();
+    let result = loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match timeout(remaining, framed.next()).await {
+            Ok(Some(Ok(bytes))) => {
+                buf.extend_from_slice(&bytes);
+                if accept(&buf) {
+                    break Ok(true);
+                }
+            }
+            Ok(Some(Err(e))) => break Err(AppError::io(Device::System, e)),
+            Ok(None) | Err(_) => break Ok(false),
+        }
+    };
+    // 记录累积的完整应答，而不是串口分段收到的数据块
+    if !buf.is_empty() {
+        capture.record(Device::System, Direction::Rx, &buf);
+    }
+    result
+}
+
+async fn probe_port(
+    capture: &Capture,
+    port: &str,
+    hall: &SerialSettings,
+    motor: &SerialSettings,
+) -> AppResult<Option<Device>> {
+    let hall_request = [0xFF, 0xEE, 0xAA, 0xEF, 0xFE];
+    if exchange(capture, port, hall, &hall_request, hall_frame_valid).await? {
+        return Ok(Some(Device::Hall));
+    }
+    // 命令 4 只读取当前角度，不会让电机动作
+    let motor_request = [0xEF, 0xFE, 0x04, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xEE];
+    let is_motor = |b: &[u8]| parse_frame(b).is_ok();
+    if exchange(capture, port, motor, &motor_request, is_motor).await? {
+        return Ok(Some(Device::Motor));
+    }
+    Ok(None)
+}
+
+/// 逐个探测可用串口上连接的是霍尔板还是电机控制器，remember 为真时记录对应的设备标识
+pub async fn probe_ports(app: &AppWrapper, remember: bool) -> AppResult<Vec<ProbeResult>> {
+    let ports = tokio_serial::available_ports().map_err(|e| AppError::State(format!("获取串口列表失败: {}", e)))?;
+    let (hall_settings, motor_settings) = {
+        let settings = app.settings.lock().await;
+        (settings.hall_serial.clone(), settings.motor_serial.clone())
+    };
+    let hall_port = app.hall_target.lock().await.as_ref().map(|t| t.port.clone());
+    let motor_port = app.motor_target.lock().await.as_ref().map(|t| t.port.clone());
+
+    let mut results = Vec::with_capacity(ports.len());
+    for p in ports {
+        let usb_id = usb_id_of(&p.port_name, &p.port_type);
+        let in_use = if hall_port.as_deref() == Some(p.port_name.as_str()) && is_open(app, Device::Hall).await {
+            Some(Device::Hall)
+        } else if motor_port.as_deref() == Some(p.port_name.as_str()) && is_open(app, Device::Motor).await {
+            Some(Device::Motor)
+        } else {
+            None
+        };
+        if let Some(device) = in_use {
+            results.push(ProbeResult {
+                port: p.port_name,
+                device: Some(device),
+                usb_id,
+                in_use: true,
+                message: format!("已作为{}连接", device.label()),
+            });
+            continue;
+        }
+        let (device, message) = match probe_port(&app.capture, &p.port_name, &hall_settings, &motor_settings).await {
+            Ok(Some(device)) => (Some(device), format!("检测到{}", device.label())),
+            Ok(None) => (None, "无应答".to_string()),
+            Err(e) => (None, e.to_string()),
+        };
+        results.push(ProbeResult {
+            port: p.port_name,
+            device,
+            usb_id,
+            in_use: false,
+            message,
+        });
+    }
+
+    if remember {
+        let hall_id = results.iter().find(|r| r.device == Some(Device::Hall)).and_then(|r| r.usb_id.clone());
+        let motor_id = results.iter().find(|r| r.device == Some(Device::Motor)).and_then(|r| r.usb_id.clone());
+        app.remember_usb_ids(hall_id, motor_id).await?;
+    }
+    Ok(results)
+}
+
+/// 网络中应答发现请求的激光扫描仪，扫描仪协议没有查询型号和序列号的命令，只能给出应答的地址
+#[derive(Clone, Serialize)]
+pub struct LaserScanner {
+    pub ip: String,
+    pub port: u16,
+    /// 可直接填入 laser_addr 的地址
+    pub address: String,
+    /// 收到应答的帧数
+    pub frames: u32,
+}
+
+/// 向局域网广播激光取数命令，按应答来源汇总扫描仪列表。
+/// 扫描仪协议没有单独的识别命令，凡是对取数命令作出应答的地址都视为扫描仪，结果中没有型号和序列号
+pub async fn discover_lasers(
+    app: &AppWrapper,
+    port: Option<u16>,
+    broadcast: Option<Vec<String>>,
+) -> AppResult<Vec<LaserScanner>> {
+    let port = match port {
+        Some(p) => p,
+        None => app
+            .laser_address
+            .lock()
+            .await
+            .as_deref()
+            .and_then(|a| a.parse::<SocketAddr>().ok())
+            .map(|a| a.port())
+            .ok_or_else(|| AppError::InvalidInput("请指定激光扫描仪的端口".into()))?,
+    };
+    let mut targets = vec![SocketAddr::from((Ipv4Addr::BROADCAST, port))];
+    for addr in broadcast.unwrap_or_default() {
+        let ip: Ipv4Addr = addr
+            .trim()
+            .parse()
+            .map_err(|_| AppError::InvalidInput(format!("广播地址{}格式不正确", addr)))?;
+        targets.push(SocketAddr::from((ip, port)));
+    }
+
+    // 使用系统分配的端口，避免与已连接的激光套接字冲突
+    let local = app.settings.lock().await.laser.ephemeral_addr()?;
+    let socket = UdpSocket::bind(local).await.map_err(|e| AppError::io(Device::Laser, e))?;
+    socket.set_broadcast(true).map_err(|e| AppError::io(Device::Laser, e))?;
+    let query: [u8; 8] = [0xAA, 0x55, 0x55, 0xAA, 0x02, 0x00, 0x21, 0x01];
+    for target in &targets {
+        if let Err(e) = socket.send_to(&query, target).await {
+            warn!("Laser discovery send to {} failed: {}", target, e);
+        }
+    }
+
+    let mut found: Vec<LaserScanner> = Vec::new();
+    let deadline = Instant::now() + DISCOVER_WINDOW;
+    let mut buf = [0u8; 2048];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let from = match timeout(remaining, socket.recv_from(&mut buf)).await {
+            Ok(Ok((len, from))) if len > 0 => from,
+            Ok(Ok(_)) => continue,
+            Ok(Err(e)) => {
+                // Windows 下目标不可达会以 ConnectionReset 报告，忽略后继续等待
+                warn!("Laser discovery recv failed: {}", e);
+                continue;
+            }
+            Err(_) => break,
+        };
+        match found.iter_mut().find(|s| s.address == from.to_string()) {
+            Some(scanner) => scanner.frames += 1,
+            None => found.push(LaserScanner {
+                ip: from.ip().to_string(),
+                port: from.port(),
+                address: from.to_string(),
+                frames: 1,
+            }),
+        }
+    }
+    Ok(found)
+}
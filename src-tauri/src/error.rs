@@ -0,0 +1,125 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+/// 出错的设备或子系统
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Device {
+    Hall,
+    Motor,
+    Laser,
+    Database,
+    File,
+    System,
+}
+
+impl Device {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Device::Hall => "霍尔传感器",
+            Device::Motor => "电机控制器",
+            Device::Laser => "激光传感器",
+            Device::Database => "数据库",
+            Device::File => "文件",
+            Device::System => "系统",
+        }
+    }
+}
+
+/// 后端统一的错误类型，序列化后前端可按 code 区分超时、断线等情况
+#[derive(Clone, Debug)]
+pub enum AppError {
+    /// 设备未连接或连接已断开
+    Disconnected(Device),
+    /// 串口或 UDP 读写失败
+    Io { device: Device, message: String },
+    /// 等待设备响应超时
+    Timeout(Device),
+    /// 响应帧格式不符合协议
+    Protocol { device: Device, message: String },
+    /// 数据内容无法解析
+    Parse { device: Device, message: String },
+    Database(String),
+    Export(String),
+    /// 参数不合法
+    InvalidInput(String),
+    /// 当前状态下不允许该操作
+    State(String),
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn io(device: Device, e: impl fmt::Display) -> Self {
+        AppError::Io { device, message: e.to_string() }
+    }
+
+    pub fn protocol(device: Device, message: impl Into<String>) -> Self {
+        AppError::Protocol { device, message: message.into() }
+    }
+
+    pub fn parse(device: Device, message: impl Into<String>) -> Self {
+        AppError::Parse { device, message: message.into() }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Disconnected(_) => "disconnected",
+            AppError::Io { .. } => "io",
+            AppError::Timeout(_) => "timeout",
+            AppError::Protocol { .. } => "protocol",
+            AppError::Parse { .. } => "parse",
+            AppError::Database(_) => "database",
+            AppError::Export(_) => "export",
+            AppError::InvalidInput(_) => "invalid_input",
+            AppError::State(_) => "state",
+        }
+    }
+
+    pub fn device(&self) -> Device {
+        match self {
+            AppError::Disconnected(d) | AppError::Timeout(d) => *d,
+            AppError::Io { device, .. }
+            | AppError::Protocol { device, .. }
+            | AppError::Parse { device, .. } => *device,
+            AppError::Database(_) => Device::Database,
+            AppError::Export(_) => Device::File,
+            AppError::InvalidInput(_) | AppError::State(_) => Device::System,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Disconnected(d) => write!(f, "{}未连接", d.label()),
+            AppError::Io { device, message } => write!(f, "{}通信错误: {}", device.label(), message),
+            AppError::Timeout(d) => write!(f, "{}响应超时，请检查线路连接！", d.label()),
+            AppError::Protocol { device, message } => write!(f, "{}响应错误: {}", device.label(), message),
+            AppError::Parse { device, message } => write!(f, "{}数据解析失败: {}", device.label(), message),
+            AppError::Database(message) => write!(f, "数据库异常: {}", message),
+            AppError::Export(message) => write!(f, "导出失败: {}", message),
+            AppError::InvalidInput(message) => write!(f, "参数错误: {}", message),
+            AppError::State(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("AppError", 3)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("device", &self.device())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.end()
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        AppError::Database(e.to_string())
+    }
+}
@@ -0,0 +1,268 @@
+use crate::serial::LaserData;
+use log::warn;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::time::Instant;
+
+/// 同一类事件的最小发送间隔，避免高频采集时前端来不及渲染
+const EVENT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 单个角度的霍尔采样，data 为原始读数，volts 为换算后的电压
+#[derive(Clone, Serialize)]
+pub struct HallSampleEvent {
+    pub angle: f32,
+    pub data: Vec<i32>,
+    pub volts: Vec<f32>,
+    pub std: Vec<f32>,
+}
+
+/// 单个角度的激光轮廓摘要，完整点云写入文件
+#[derive(Clone, Serialize)]
+pub struct LaserSummaryEvent {
+    pub angle: f32,
+    pub points: usize,
+    pub r_min: f32,
+    pub r_max: f32,
+    pub r_mean: f32,
+    pub z_min: f32,
+    pub z_max: f32,
+}
+
+impl LaserSummaryEvent {
+    pub fn new(angle: f32, points: &[LaserData]) -> Self {
+        let mut summary = LaserSummaryEvent {
+            angle,
+            points: points.len(),
+            r_min: 0.0,
+            r_max: 0.0,
+            r_mean: 0.0,
+            z_min: 0.0,
+            z_max: 0.0,
+        };
+        if points.is_empty() {
+            return summary;
+        }
+        let radius: Vec<f32> = points.iter().map(|p| p.x.hypot(p.y)).collect();
+        summary.r_min = radius.iter().cloned().fold(f32::INFINITY, f32::min);
+        summary.r_max = radius.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        summary.r_mean = radius.iter().sum::<f32>() / radius.len() as f32;
+        summary.z_min = points.iter().map(|p| p.z).fold(f32::INFINITY, f32::min);
+        summary.z_max = points.iter().map(|p| p.z).fold(f32::NEG_INFINITY, f32::max);
+        summary
+    }
+}
+
+/// 新采集的激光轮廓，间隔内的事件合并成数组发送，界面按序号调用 get_laser_profile 取完整点云
+#[derive(Clone, Serialize)]
+pub struct LaserProfileEvent {
+    pub seq: u64,
+    pub angle: f32,
+}
+
+#[derive(Clone, Serialize)]
+pub struct MotorPositionEvent {
+    pub angle: f32,
+}
+
+/// 检测进度，percent 为已转过的角度占一圈的百分比
+#[derive(Clone, Serialize)]
+pub struct SessionProgressEvent {
+    pub parent_id: i64,
+    pub angle: f32,
+    pub samples: u64,
+    pub percent: f32,
+    pub elapsed_s: f32,
+    /// 按当前转速估算的剩余秒数，尚无法估算时为空
+    pub eta_s: Option<f32>,
+    pub finished: bool,
+}
+
+/// 一类事件的节流状态
+struct Slot {
+    event: &'static str,
+    /// 为真时间隔内的数据合并成数组发送，否则只发送最新一次
+    batch: bool,
+    last: Option<Instant>,
+    /// 间隔内到达、尚未发送的数据
+    pending: Vec<Value>,
+}
+
+impl Slot {
+    /// 记录一次数据，距上次发送已满间隔时返回要发送的内容
+    fn push(&mut self, value: Value, now: Instant) -> Option<Value> {
+        if !self.batch {
+            self.pending.clear();
+        }
+        self.pending.push(value);
+        if self.last.is_some_and(|last| now.duration_since(last) < EVENT_INTERVAL) {
+            return None;
+        }
+        self.last = Some(now);
+        self.take()
+    }
+
+    fn take(&mut self) -> Option<Value> {
+        let mut pending = std::mem::take(&mut self.pending);
+        if self.batch {
+            (!pending.is_empty()).then_some(Value::Array(pending))
+        } else {
+            pending.pop()
+        }
+    }
+}
+
+/// 按事件名节流发送，间隔内的数据暂存，到下一次发送或 flush 时补发
+pub struct Throttle {
+    app: AppHandle,
+    slots: Vec<Slot>,
+}
+
+impl Throttle {
+    pub fn new(app: AppHandle) -> Self {
+        Throttle { app, slots: Vec::new() }
+    }
+
+    /// 间隔内只保留最新一次的数据
+    pub fn emit<S: Serialize>(&mut self, event: &'static str, payload: S) {
+        self.push(event, payload, false);
+    }
+
+    /// 间隔内的数据合并成数组发送，不丢弃
+    pub fn emit_batch<S: Serialize>(&mut self, event: &'static str, payload: S) {
+        self.push(event, payload, true);
+    }
+
+    fn push<S: Serialize>(&mut self, event: &'static str, payload: S, batch: bool) {
+        let value = match serde_json::to_value(payload) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Failed to serialize {}: {}", event, e);
+                return;
+            }
+        };
+        let index = match self.slots.iter().position(|s| s.event == event) {
+            Some(i) => i,
+            None => {
+                self.slots.push(Slot {
+                    event,
+                    batch,
+                    last: None,
+                    pending: Vec::new(),
+                });
+                self.slots.len() - 1
+            }
+        };
+        if let Some(value) = self.slots[index].push(value, Instant::now()) {
+            self.emit_now(event, value);
+        }
+    }
+
+    /// 发送所有暂存的数据，采集结束时调用
+    pub fn flush(&mut self) {
+        for slot in &mut self.slots {
+            if let Some(value) = slot.take() {
+                if let Err(e) = self.app.emit(slot.event, value) {
+                    warn!("Failed to emit {}: {}", slot.event, e);
+                }
+            }
+        }
+    }
+
+    pub fn emit_now<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        if let Err(e) = self.app.emit(event, payload) {
+            warn!("Failed to emit {}: {}", event, e);
+        }
+    }
+}
+
+/// 根据电机角度累计转过的角度并估算剩余时间
+pub struct Progress {
+    parent_id: i64,
+    start: Instant,
+    last_angle: Option<f32>,
+    travelled: f32,
+    samples: u64,
+}
+
+impl Progress {
+    pub fn new(parent_id: i64) -> Self {
+        Progress {
+            parent_id,
+            start: Instant::now(),
+            last_angle: None,
+            travelled: 0.0,
+            samples: 0,
+        }
+    }
+
+    pub fn update(&mut self, angle: f32) -> SessionProgressEvent {
+        if let Some(last) = self.last_angle {
+            // 角度只会向前走，跨过 360° 时取模后累加
+            self.travelled += (angle - last).rem_euclid(360.0);
+        }
+        self.last_angle = Some(angle);
+        self.samples += 1;
+        self.event(false)
+    }
+
+    pub fn event(&self, finished: bool) -> SessionProgressEvent {
+        let elapsed_s = self.start.elapsed().as_secs_f32();
+        let percent = (self.travelled / 360.0 * 100.0).min(100.0);
+        let eta_s = if finished {
+            Some(0.0)
+        } else if self.travelled > 0.0 {
+            Some(elapsed_s * (360.0 - self.travelled).max(0.0) / self.travelled)
+        } else {
+            None
+        };
+        SessionProgressEvent {
+            parent_id: self.parent_id,
+            angle: self.last_angle.unwrap_or(0.0),
+            samples: self.samples,
+            percent,
+            elapsed_s,
+            eta_s,
+            finished,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn slot(batch: bool) -> Slot {
+        Slot {
+            event: "test",
+            batch,
+            last: None,
+            pending: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn latest_payload_is_kept_within_interval() {
+        let mut slot = slot(false);
+        let t0 = Instant::now();
+        assert_eq!(slot.push(json!(1), t0), Some(json!(1)));
+        assert_eq!(slot.push(json!(2), t0 + Duration::from_millis(30)), None);
+        assert_eq!(slot.push(json!(3), t0 + Duration::from_millis(60)), None);
+        // 间隔内最后一次的数据在 flush 时补发
+        assert_eq!(slot.take(), Some(json!(3)));
+        assert_eq!(slot.take(), None);
+    }
+
+    #[test]
+    fn batch_collects_every_payload() {
+        let mut slot = slot(true);
+        let t0 = Instant::now();
+        assert_eq!(slot.push(json!(1), t0), Some(json!([1])));
+        assert_eq!(slot.push(json!(2), t0 + Duration::from_millis(30)), None);
+        assert_eq!(slot.push(json!(3), t0 + Duration::from_millis(60)), None);
+        assert_eq!(slot.push(json!(4), t0 + Duration::from_millis(120)), Some(json!([2, 3, 4])));
+        assert_eq!(slot.take(), None);
+    }
+}
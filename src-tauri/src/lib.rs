@@ -1,22 +1,52 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
+mod acquisition;
+mod buffer;
+mod capture;
+mod device;
+mod error;
+mod events;
+mod logging;
+mod motion;
+mod motor;
+mod replay;
+mod safety;
 mod serial;
+mod settings;
 mod sqlite;
+mod verdict;
 
+use crate::buffer::{RingSlice, SeqRing, Sequenced};
+use crate::capture::{Capture, Direction};
+use crate::device::{spawn_health_monitor, spawn_port_monitor, SerialTarget};
+use crate::error::{AppError, AppResult, Device};
+use crate::events::MotorPositionEvent;
 use crate::serial::{
-    deinit_device, fetch_hall_data, get_hall, get_laser, get_motor_angle, get_port, hall_parse_data,
-    init_device, motor_start_d, motor_start_one_circle, motor_start_u, motor_stop, rotate_motor,
-    set_motor_calibrated, set_motor_single_angle, set_motor_single_circle_pulse, set_motor_speed, start_work, stop_work,
+    apply_motion_profile, connect_hall, connect_laser, connect_motor, decode_capture, deinit_device, disconnect_hall,
+    disconnect_laser, disconnect_motor, discover_lasers, fetch_hall_data, fetch_hall_since, fetch_laser_profiles,
+    fetch_logs, get_device_status, get_hall, get_laser, get_laser_profile, get_motor_angle, get_port, get_settings,
+    hall_last_revolution, hall_parse_data, hall_statistics, home_motor, init_device, motor_start_d,
+    motor_start_one_circle, motor_start_u, motor_stop, move_to_angle, probe_ports, rotate_motor, set_log_level,
+    set_motion_profile, set_motor_calibrated, set_motor_single_angle, set_motor_single_circle_pulse, set_motor_speed,
+    set_settings, start_capture, start_replay, start_work, stop_capture, stop_work, sync_motor_params, HallStat,
+    LaserData,
 };
-use crate::sqlite::{connect_to_db, gen_xlsx, get_data_by_parent_id};
-use futures::{SinkExt, StreamExt};
+use crate::motion::angle_in_range;
+use crate::motor::{MotorEvent, MotorFrameCodec, MotorLink, EVENT_CHANNEL_SIZE, REPORT_COMMAND};
+use crate::safety::{acknowledge_fault, emergency_stop, get_interlock, spawn_interlock_monitor, InterlockState};
+use crate::settings::{settings_path, AppSettings, LaserSettings, MotorSettings, PulseSyncPolicy, SerialSettings, UsbId};
+use crate::sqlite::{gen_xlsx, get_data_by_parent_id, get_project_verdict, get_stat_by_parent_id, init_db};
+use futures::{SinkExt, Stream, StreamExt};
+use log::{debug, info, trace, warn};
 use serde::Serialize;
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::time::Duration;
 use tauri::{AppHandle, Emitter, Manager};
 use tokio::net::UdpSocket;
-use tokio::sync::{mpsc, watch, Mutex};
+use tokio::sync::{broadcast, mpsc, watch, Mutex};
 use tokio::time::timeout;
 use tokio_serial::{SerialPortBuilderExt, SerialStream};
 use tokio_util::bytes::{Bytes, BytesMut};
@@ -33,147 +63,362 @@ pub struct AppWrapper {
     pub step_pulse: Mutex<u32>,
     single_circle_pulse: Mutex<u32>,
     pub hall_serial: Mutex<Option<Framed<SerialStream, BytesCodec>>>,
-    pub motor_serial: Mutex<Option<Framed<SerialStream, BytesCodec>>>,
+    /// 电机串口由链路任务独占，这里只保存向它排队发送命令的句柄
+    pub motor: Mutex<Option<MotorLink>>,
+    /// 控制器主动上报的角度、联锁等数据
+    pub motor_events: broadcast::Sender<MotorEvent>,
     pub laser_address: Mutex<Option<String>>,
     pub laser_socket: Mutex<Option<UdpSocket>>,
     pub stop_tx: watch::Sender<bool>,
-    hall_buffer: Mutex<VecDeque<Payload>>,
-    pub motor_tx: mpsc::Sender<f32>,
-    pub motor_rx: Mutex<mpsc::Receiver<f32>>,
-
+    hall_buffer: Mutex<SeqRing<Payload>>,
+    laser_buffer: Mutex<SeqRing<LaserProfile>>,
+    pub hall_target: Mutex<Option<SerialTarget>>,
+    pub motor_target: Mutex<Option<SerialTarget>>,
+    /// 采集进行中时健康监测不主动访问设备
+    pub scanning: AtomicBool,
+    /// 回零或定位进行中，期间不允许开始采集，健康监测也不主动访问设备
+    pub moving: AtomicBool,
+    /// 健康监测探测设备期间持有，占用设备前先获取，避免检查标志和发送探测之间开始采集
+    pub probe_lock: Mutex<()>,
+    pub settings: Mutex<AppSettings>,
+    settings_path: PathBuf,
+    pub interlock: Mutex<InterlockState>,
+    /// 设备收发原始数据记录
+    pub capture: Arc<Capture>,
 }
 impl AppWrapper {
-    /// 初始化霍尔串口和电机串口
+    /// 按需连接霍尔、电机和激光，任一设备失败时释放本次已打开的设备
     pub async fn init(
         &self,
-        hall_port: &str,
-        motor_port: &str,
-        laser_addr: String,
-    ) -> Result<String, String> {
-        // 初始化霍尔串口
-        let hall = tokio_serial::new(hall_port, 115200)
+        hall_port: Option<String>,
+        motor_port: Option<String>,
+        laser_addr: Option<String>,
+        hall_serial: Option<SerialSettings>,
+        motor_serial: Option<SerialSettings>,
+        laser_bind: Option<LaserSettings>,
+    ) -> AppResult<String> {
+        let mut opened = Vec::new();
+        let result = async {
+            if let Some(port) = hall_port {
+                self.connect_hall(&port, hall_serial).await?;
+                opened.push(Device::Hall);
+            }
+            if let Some(port) = motor_port {
+                self.connect_motor(&port, motor_serial).await?;
+                opened.push(Device::Motor);
+            }
+            if let Some(addr) = laser_addr {
+                self.connect_laser(addr, laser_bind).await?;
+                opened.push(Device::Laser);
+            }
+            Ok::<(), AppError>(())
+        }
+        .await;
+        if let Err(e) = result {
+            for device in opened {
+                self.disconnect_device(device).await;
+            }
+            return Err(e);
+        }
+        if opened.is_empty() {
+            return Err(AppError::InvalidInput("未选择任何设备".into()));
+        }
+        if opened.contains(&Device::Motor) {
+            self.sync_motor_params().await;
+            self.restore_motor_speed().await;
+        }
+        Ok("连接成功!".to_string())
+    }
+    /// 修改设置副本，转速按运动曲线限幅并校验通过后写入文件再替换内存中的设置
+    pub async fn update_settings(&self, f: impl FnOnce(&mut AppSettings)) -> AppResult<()> {
+        let mut settings = self.settings.lock().await;
+        let mut updated = settings.clone();
+        f(&mut updated);
+        updated.normalize();
+        updated.validate()?;
+        if updated == *settings {
+            return Ok(());
+        }
+        updated.save(&self.settings_path)?;
+        *settings = updated;
+        Ok(())
+    }
+    /// 保存运行中产生的设置，失败时只记录日志，不影响当前操作
+    async fn remember_settings(&self, f: impl FnOnce(&mut AppSettings)) {
+        if let Err(e) = self.update_settings(f).await {
+            warn!("Failed to save settings: {}", e);
+        }
+    }
+    /// 记录自动检测到的设备标识，未检测到的保留原值
+    pub async fn remember_usb_ids(&self, hall: Option<UsbId>, motor: Option<UsbId>) -> AppResult<()> {
+        self.update_settings(|s| {
+            if hall.is_some() {
+                s.hall_usb_id = hall;
+            }
+            if motor.is_some() {
+                s.motor_usb_id = motor;
+            }
+        })
+        .await
+    }
+    /// 用新的设置替换全部设置，电机脉冲参数同时应用到当前状态
+    ///
+    /// 电机已连接时先把改动的脉冲参数和转速写入控制器，写入失败则不保存，
+    /// 否则下次连接按控制器同步参数时会把修改还原。
+    pub async fn set_settings(&self, mut settings: AppSettings) -> AppResult<()> {
+        settings.normalize();
+        settings.validate()?;
+        let motor = settings.motor.clone();
+        let log = settings.log.clone();
+        if self.motor_link().await.is_ok() {
+            let old = self.settings.lock().await.motor.clone();
+            self.write_changed_motor_params(&old, &motor).await?;
+        }
+        self.update_settings(|s| *s = settings).await?;
+        self.apply_motor_params(motor.step_pulse, motor.single_circle_pulse).await;
+        logging::apply(&log)
+    }
+    /// 把与 old 不同的电机参数写入控制器，使用与单独设置各参数相同的命令
+    async fn write_changed_motor_params(&self, old: &MotorSettings, new: &MotorSettings) -> AppResult<()> {
+        let timeout = self.motor_timeout().await;
+        if new.single_circle_pulse != old.single_circle_pulse {
+            self.talk_with_motor(1, new.single_circle_pulse, timeout).await?;
+            *self.single_circle_pulse.lock().await = new.single_circle_pulse;
+        }
+        if new.step_pulse != old.step_pulse {
+            self.talk_with_motor(0, new.step_pulse, timeout).await?;
+            *self.step_pulse.lock().await = new.step_pulse;
+        }
+        // 转速按单圈脉冲换算成脉冲频率，单圈脉冲改变时也要重新写入
+        if new.speed != old.speed || new.single_circle_pulse != old.single_circle_pulse {
+            self.write_motor_speed(new.speed).await?;
+        }
+        Ok(())
+    }
+    /// 修改日志级别并立即生效，未指定模块时修改默认级别
+    pub async fn set_log_level(&self, module: Option<String>, level: String) -> AppResult<()> {
+        self.update_settings(|s| match module {
+            Some(module) => {
+                s.log.modules.insert(module, level);
+            }
+            None => s.log.level = level,
+        })
+        .await?;
+        logging::apply(&self.settings.lock().await.log)
+    }
+    async fn hall_timeout(&self) -> Duration {
+        self.settings.lock().await.hall_serial.timeout()
+    }
+    async fn motor_timeout(&self) -> Duration {
+        self.settings.lock().await.motor_serial.timeout()
+    }
+    /// 打开霍尔串口，并记录端口供断线重连使用，新的串口参数在打开成功后才保存
+    pub async fn connect_hall(&self, port: &str, serial: Option<SerialSettings>) -> AppResult<()> {
+        let serial = match serial {
+            Some(serial) => serial.validate().map(|_| serial)?,
+            None => self.settings.lock().await.hall_serial.clone(),
+        };
+        let hall = serial
+            .builder(port)
             .open_native_async()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::io(Device::Hall, e))?;
         let hall_framed = Framed::new(hall, BytesCodec::new());
         *self.hall_serial.lock().await = Some(hall_framed);
-
-        // 初始化电机串口
-        let motor = tokio_serial::new(motor_port, 115200)
+        *self.hall_target.lock().await = Some(SerialTarget::lookup(port));
+        self.remember_settings(|s| {
+            s.hall_port = Some(port.to_string());
+            s.hall_serial = serial;
+        })
+        .await;
+        Ok(())
+    }
+    /// 打开电机串口，并记录端口供断线重连使用，新的串口参数在打开成功后才保存
+    pub async fn connect_motor(&self, port: &str, serial: Option<SerialSettings>) -> AppResult<()> {
+        let serial = match serial {
+            Some(serial) => serial.validate().map(|_| serial)?,
+            None => self.settings.lock().await.motor_serial.clone(),
+        };
+        let motor = serial
+            .builder(port)
             .open_native_async()
-            .map_err(|e| e.to_string())?;
-        let motor_framed = Framed::new(motor, BytesCodec::new());
-        *self.motor_serial.lock().await = Some(motor_framed);
-        *self.laser_address.lock().await = Some(laser_addr.clone());
-        let socket = UdpSocket::bind("0.0.0.0:43000")
-            .await
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::io(Device::Motor, e))?;
+        let framed = Framed::new(motor, MotorFrameCodec);
+        let link = MotorLink::spawn(framed, self.motor_events.clone(), self.capture.clone(), safety::CMD_ESTOP);
+        *self.motor.lock().await = Some(link);
+        *self.motor_target.lock().await = Some(SerialTarget::lookup(port));
+        self.remember_settings(|s| {
+            s.motor_port = Some(port.to_string());
+            s.motor_serial = serial;
+        })
+        .await;
+        Ok(())
+    }
+    /// 按设置绑定本地地址后连接激光，端口被占用时视设置改用系统分配的端口，新的绑定设置在连接成功后才保存
+    pub async fn connect_laser(&self, laser_addr: String, bind: Option<LaserSettings>) -> AppResult<()> {
+        let laser = match bind {
+            Some(laser) => laser.validate().map(|_| laser)?,
+            None => self.settings.lock().await.laser.clone(),
+        };
+        let local = laser.socket_addr()?;
+        let socket = match UdpSocket::bind(local).await {
+            Ok(socket) => socket,
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+                if !laser.allow_ephemeral || laser.bind_port == 0 {
+                    return Err(AppError::State(format!(
+                        "本地端口{}已被占用，请关闭占用该端口的程序或更换绑定端口",
+                        local
+                    )));
+                }
+                info!("Laser bind port {} in use, falling back to ephemeral port", local);
+                UdpSocket::bind(laser.ephemeral_addr()?)
+                    .await
+                    .map_err(|e| AppError::io(Device::Laser, e))?
+            }
+            Err(e) => return Err(AppError::io(Device::Laser, format!("绑定{}失败: {}", local, e))),
+        };
         socket
-            .connect(laser_addr)
+            .connect(&laser_addr)
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::io(Device::Laser, e))?;
         *self.laser_socket.lock().await = Some(socket);
-        Ok("连接成功!".to_string())
+        self.remember_settings(|s| {
+            s.laser_addr = Some(laser_addr.clone());
+            s.laser = laser;
+        })
+        .await;
+        *self.laser_address.lock().await = Some(laser_addr);
+        Ok(())
     }
-    pub async fn deinit(&self) -> Result<String, String> {
-        // 释放霍尔串口
-        {
-            let mut hall_lock = self.hall_serial.lock().await;
-            if hall_lock.is_some() {
-                *hall_lock = None; // Framed<T> 实现了 Drop，会自动关闭串口
-                println!("Hall serial deinitialized");
+    /// 只释放设备句柄，保留连接目标，供断线重连使用
+    pub async fn close_device(&self, device: Device) {
+        match device {
+            Device::Hall => {
+                let mut hall_lock = self.hall_serial.lock().await;
+                if hall_lock.is_some() {
+                    *hall_lock = None; // Framed<T> 实现了 Drop，会自动关闭串口
+                    info!("Hall serial deinitialized");
+                }
             }
-        }
-
-        // 释放电机串口
-        {
-            let mut motor_lock = self.motor_serial.lock().await;
-            if motor_lock.is_some() {
-                *motor_lock = None;
-                println!("Motor serial deinitialized");
+            Device::Motor => {
+                let mut motor_lock = self.motor.lock().await;
+                if motor_lock.is_some() {
+                    *motor_lock = None; // 句柄释放后链路任务退出并关闭串口
+                    info!("Motor serial deinitialized");
+                }
             }
-        }
-
-        // 释放激光 UDP socket
-        {
-            let mut socket_lock = self.laser_socket.lock().await;
-            if socket_lock.is_some() {
-                *socket_lock = None;
-                println!("Laser socket deinitialized");
+            Device::Laser => {
+                let mut socket_lock = self.laser_socket.lock().await;
+                if socket_lock.is_some() {
+                    *socket_lock = None;
+                    info!("Laser socket deinitialized");
+                }
             }
+            _ => {}
         }
-
-        // 清空激光地址
-        {
-            let mut addr_lock = self.laser_address.lock().await;
-            *addr_lock = None;
+    }
+    /// 释放设备并清空连接目标，健康监测不再尝试重连
+    pub async fn disconnect_device(&self, device: Device) {
+        self.close_device(device).await;
+        match device {
+            Device::Hall => *self.hall_target.lock().await = None,
+            Device::Motor => *self.motor_target.lock().await = None,
+            Device::Laser => *self.laser_address.lock().await = None,
+            _ => {}
         }
-
+    }
+    pub async fn deinit(&self) -> AppResult<String> {
+        self.disconnect_device(Device::Hall).await;
+        self.disconnect_device(Device::Motor).await;
+        self.disconnect_device(Device::Laser).await;
         Ok("断开成功!".to_string())
     }
-    pub async fn recv_with_timeout(
-        serial: &mut Framed<SerialStream, BytesCodec>,
-        duration: Duration,
-    ) -> Result<BytesMut, String> {
+    pub async fn recv_with_timeout<S>(serial: &mut S, duration: Duration, device: Device) -> AppResult<BytesMut>
+    where
+        S: Stream<Item = std::io::Result<BytesMut>> + Unpin,
+    {
         match timeout(duration, serial.next()).await {
             Ok(Some(Ok(bytes))) => Ok(bytes),              // 成功返回数据
-            Ok(Some(Err(e))) => Err(format!("串口接收错误: {}", e)),
-            Ok(None) => Err("串口已关闭".to_string()),      // Stream 已结束
-            Err(_) => Err("接收超时".to_string()),          // 超时
+            Ok(Some(Err(e))) => Err(AppError::io(device, e)),
+            Ok(None) => Err(AppError::Disconnected(device)), // Stream 已结束
+            Err(_) => Err(AppError::Timeout(device)),        // 超时
         }
     }
-    pub async fn spawn_motor_listener(self: Arc<Self>) {
-        let stop_rx = self.stop_tx.subscribe();
-        let tx = self.motor_tx.clone();
+    /// 向前端发送 message 事件，发送失败只记录日志
+    pub fn notify(&self, _type: &str, title: &str, message: impl Into<String>) {
+        let payload = MessagePayload {
+            title: title.to_string(),
+            message: message.into(),
+            _type: _type.to_string(),
+        };
+        if let Err(e) = self.app_handler.emit("message", payload) {
+            warn!("Failed to emit message: {}", e);
+        }
+    }
+    /// 采集期间把控制器上报的角度转发给采集任务，收到停止信号后结束检测
+    ///
+    /// 每次检测使用新的通道，上一次检测停止后才到达的角度不会被下一次检测当作触发。
+    /// events 需在发送开始检测命令之前订阅，否则应答之前上报的角度和结束帧会丢失。
+    pub async fn spawn_motor_listener(
+        self: Arc<Self>,
+        mut events: broadcast::Receiver<MotorEvent>,
+    ) -> mpsc::Receiver<AppResult<f32>> {
+        let mut stop_rx = self.stop_tx.subscribe();
+        let (tx, rx) = mpsc::channel(MOTOR_CHANNEL_SIZE);
 
         tokio::spawn(async move {
             loop {
-                if *stop_rx.borrow() {
-                    println!("Motor listener stopping...");
+                if *stop_rx.borrow_and_update() {
+                    info!("Motor listener stopping...");
                     match self.motor_stop_work().await {
-                        Ok(str) => {
-                            self.app_handler.emit("message", MessagePayload {
-                                title: "关闭成功".to_string(),
-                                message: str,
-                                _type: "success".to_string(),
-                            }).unwrap();
-                        }
-                        Err(e) => {
-                            self.app_handler.emit("message", MessagePayload {
-                                title: "关闭失败".to_string(),
-                                message: e,
-                                _type: "error".to_string(),
-                            }).unwrap();
-                        }
+                        Ok(str) => self.notify("success", "关闭成功", str),
+                        Err(e) => self.notify("error", "关闭失败", e.to_string()),
                     }
                     break;
                 }
-                match self.recv_res(Duration::from_secs(4)).await {
+                let event = tokio::select! {
+                    _ = stop_rx.changed() => continue,
+                    event = timeout(Duration::from_secs(4), events.recv()) => event,
+                };
+                let result = match event {
+                    Ok(Ok(MotorEvent::Angle(angle))) => Ok(angle),
+                    Ok(Ok(MotorEvent::Finished)) => {
+                        let _ = self.stop_tx.send(true);
+                        continue;
+                    }
+                    Ok(Ok(MotorEvent::Interlock(_))) => continue,
+                    Ok(Ok(MotorEvent::Closed(e))) => Err(e),
+                    Ok(Err(broadcast::error::RecvError::Lagged(n))) => {
+                        warn!("Motor listener lagged {} events", n);
+                        continue;
+                    }
+                    Ok(Err(broadcast::error::RecvError::Closed)) => Err(AppError::Disconnected(Device::Motor)),
+                    Err(_) => Err(AppError::Timeout(Device::Motor)),
+                };
+                match result {
                     Ok(angle) => {
                         // 收到角度，发到 channel
-                        if tx.send(angle).await.is_err() {
-                            println!("No receiver for motor data");
+                        if tx.send(Ok(angle)).await.is_err() {
+                            debug!("No receiver for motor data");
                         }
                     }
-                    Err(_) => {
-                        // 超时不处理，继续等
-                        self.app_handler.emit("message", MessagePayload {
-                            title: "关闭失败".to_string(),
-                            message: "电机控制板通信超时！".into(),
-                            _type: "error".to_string(),
-                        }).unwrap();
+                    Err(e) => {
+                        // 通信失败交给采集任务结束本次检测
+                        let _ = tx.send(Err(e)).await;
                         break;
                     }
                 }
             }
         });
+        rx
     }
 
-    pub async fn get_hall_data(&self) -> Result<Vec<i32>, String> {
+    pub async fn get_hall_data(&self) -> AppResult<Vec<i32>> {
+        let read_timeout = self.hall_timeout().await;
         let mut lock = self.hall_serial.lock().await;
 
         let serial = match lock.as_mut() {
             Some(s) => s,
-            None => return Err("Hall serial not initialized".into()),
+            None => return Err(AppError::Disconnected(Device::Hall)),
         };
         let pkg: [u8; 5] = [0xFF, 0xEE, 0xAA, 0xEF, 0xFE];
 
@@ -181,265 +426,406 @@ impl AppWrapper {
         serial
             .send(Bytes::copy_from_slice(&pkg[..]))
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::io(Device::Hall, e))?;
+        self.capture.record(Device::Hall, Direction::Tx, &pkg);
         let mut buf = Vec::new();
         let expected_len = 44;
 
         while buf.len() < expected_len {
-            if let Some(Ok(bytes)) = timeout(Duration::from_secs(2), serial.next()).await.map_err(|e| e.to_string())? {
-                buf.extend_from_slice(&bytes);
-            } else {
-                return Err("Timeout waiting for hall data".into());
-            }
+            let bytes = Self::recv_with_timeout(serial, read_timeout, Device::Hall).await?;
+            buf.extend_from_slice(&bytes);
         }
+        // 记录拼好的完整帧，串口分段收到的数据块单独记录无法解码
+        self.capture.record(Device::Hall, Direction::Rx, &buf);
 
         // 现在 buf 一定是 >= 44，可以截取前 44
         let buf = buf[..expected_len].to_vec();
         match hall_parse_data(&buf) {
             Some(res) => Ok(res),
-            None => Err("Hall serial parse error".into()),
+            None => Err(AppError::parse(Device::Hall, "霍尔数据长度不足")),
         }
     }
 
-    pub async fn rotate_motor_pulse(&self, pulse: u32) -> Result<(), String> {
-        let mut lock = self.motor_serial.lock().await;
-        let serial = match lock.as_mut() {
-            Some(s) => s,
-            None => return Err("Motor serial not initialized".into()),
-        };
-        let mut pkg: [u8; 9] = [0xEF, 0xFE, 0x01, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xEE];
-        let bytes = pulse.to_le_bytes();
-        pkg[3..7].copy_from_slice(&bytes);
-        serial
-            .send(Bytes::copy_from_slice(&pkg[..]))
-            .await
-            .map_err(|e| e.to_string())?;
+    /// 连续采集 samples 次霍尔数据，返回各通道的均值、标准差和极值
+    pub async fn get_hall_data_averaged(&self, samples: u32) -> AppResult<HallStat> {
+        let mut readings = Vec::with_capacity(samples.max(1) as usize);
+        for _ in 0..samples.max(1) {
+            readings.push(self.get_hall_data().await?);
+        }
+        match hall_statistics(&readings) {
+            Some(stat) => Ok(stat),
+            None => Err(AppError::parse(Device::Hall, "各次采样的通道数不一致")),
+        }
+    }
 
+    pub async fn rotate_motor_pulse(&self, pulse: u32) -> AppResult<()> {
+        safety::ensure_motion_allowed(self).await?;
         // 等待返回
-        if let Some(Ok(_)) = timeout(Duration::from_secs(20), serial.next())
-            .await
-            .map_err(|_| "电机响应超时，请检查线路连接！".to_string())?
-        {
-            Ok(())
-        } else {
-            Err("No response from Motor".into())
-        }
+        self.talk_with_motor(0x01, pulse, Duration::from_secs(20)).await?;
+        Ok(())
     }
 
-    pub async fn rotate_motor_step(&self) -> Result<(), String> {
+    pub async fn rotate_motor_step(&self) -> AppResult<()> {
         self.rotate_motor_pulse(*self.step_pulse.lock().await).await
     }
 
 
-    async fn recv_res(&self, duration: Duration) -> Result<f32, String> {
-        let mut lock = self.motor_serial.lock().await;
-        let serial = match lock.as_mut() {
-            Some(s) => s,
-            None => return Err("Motor not initialized".into()),
-        };
-        match Self::recv_with_timeout(serial, duration).await {
-            Ok(bytes) => {
-                // println!("{:X}", bytes);
-                let data = &bytes[..];
-                if data[0..2] == [0xEF, 0xFE] && data[7..] == [0xFF, 0xEE] {
-                    if data[3] == 0x09 {
-                        self.stop_tx.send(true).unwrap();
-                    }
-                    let slice = u32::from_le_bytes([bytes[3], bytes[4], bytes[5], bytes[6]]);
-                    Ok(f32::from_bits(slice))
-                } else {
-                    Err("响应错误".into())
-                }
-            }
-            Err(_e) => Err("电机控制器响应超时！".into()),
+    pub async fn motor_link(&self) -> AppResult<MotorLink> {
+        match self.motor.lock().await.as_ref() {
+            Some(link) if !link.is_closed() => Ok(link.clone()),
+            _ => Err(AppError::Disconnected(Device::Motor)),
         }
     }
 
-    async fn talk_with_motor(&self, command: u8, value: u32, duration: Duration) -> Result<u32, String> {
-        let mut lock = self.motor_serial.lock().await;
-        let serial = match lock.as_mut() {
-            Some(s) => s,
-            None => return Err("Motor not initialized".into()),
-        };
-        let mut pkg: [u8; 9] = [0xEF, 0xFE, command, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xEE];
-        let bytes = value.to_le_bytes();
-        pkg[3..7].copy_from_slice(&bytes);
-        serial
-            .send(Bytes::copy_from_slice(&pkg[..]))
-            .await
-            .map_err(|e| e.to_string())?;
-        match Self::recv_with_timeout(serial, duration).await {
-            Ok(bytes) => {
-                println!("{:X}", bytes);
-                let data = &bytes[..];
-                if data[0..2] == [0xEF, 0xFE] && data[7..] == [0xFF, 0xEE] {
-                    let slice = u32::from_le_bytes([bytes[3], bytes[4], bytes[5], bytes[6]]);
-                    Ok(slice)
-                } else {
-                    Err("响应错误".into())
-                }
-            }
-            Err(_e) => Err("电机控制器响应超时！".into()),
-        }
+    async fn talk_with_motor(&self, command: u8, value: u32, duration: Duration) -> AppResult<u32> {
+        let bytes = self.motor_link().await?.request(command, value, duration).await?;
+        trace!("{:X}", bytes);
+        motor::parse_frame(&bytes[..])
     }
 
 
-    pub async fn set_motor_single_angle(&self, angle: f32) -> Result<String, String> {
+    pub async fn set_motor_single_angle(&self, angle: f32) -> AppResult<String> {
         let value = {
             let single = self.single_circle_pulse.lock().await;
             (angle * (*single) as f32 / 360.0_f32).ceil() as u32
         };
-        match self.talk_with_motor(0, value, Duration::from_millis(1000)).await {
-            Ok(_) => {
-                *self.step_pulse.lock().await = value;
-                Ok(format!("设置单步脉冲个数为{}", value))
-            }
-            _ => { Err("电机控制器响应错误，请检查线路！".into()) }
-        }
+        self.talk_with_motor(0, value, self.motor_timeout().await).await?;
+        *self.step_pulse.lock().await = value;
+        self.remember_settings(|s| s.motor.step_pulse = value).await;
+        Ok(format!("设置单步脉冲个数为{}", value))
     }
-    pub async fn set_single_circle_pulse(&self, pulse: u32) -> Result<String, String> {
-        match self.talk_with_motor(1, pulse, Duration::from_millis(1000)).await {
-            Ok(_) => {
-                let mut value = self.single_circle_pulse.lock().await;
-                *value = pulse;
-                Ok(format!("设置单圈脉冲个数为{}", pulse))
-            }
-            _ => {
-                Err("电机控制器响应错误，请检查线路！".into())
-            }
+    pub async fn set_single_circle_pulse(&self, pulse: u32) -> AppResult<String> {
+        self.talk_with_motor(1, pulse, self.motor_timeout().await).await?;
+        *self.single_circle_pulse.lock().await = pulse;
+        self.remember_settings(|s| s.motor.single_circle_pulse = pulse).await;
+        Ok(format!("设置单圈脉冲个数为{}", pulse))
+    }
+    /// 写入一个控制器参数
+    pub async fn write_motor_param(&self, command: u8, value: u32) -> AppResult<()> {
+        self.talk_with_motor(command, value, self.motor_timeout().await).await?;
+        Ok(())
+    }
+
+    pub async fn set_motor_speed(&self, speed: f32) -> AppResult<String> {
+        debug!("speed: {}", speed);
+        let max_speed = self.settings.lock().await.motion_profile().max_speed;
+        if !speed.is_finite() || speed <= 0.0 || speed > max_speed {
+            return Err(AppError::InvalidInput(format!("转速必须在0~{}RPM之间", max_speed)));
         }
+        self.write_motor_speed(speed).await?;
+        self.remember_settings(|s| s.motor.speed = speed).await;
+        Ok(format!("设置速度为{}RPM成功!", speed))
     }
-    pub async fn set_motor_speed(&self, speed: f32) -> Result<String, String> {
-        println!("speed: {}", speed);
+
+    /// 按当前单圈脉冲把转速换算为控制器的脉冲频率后写入，不修改设置
+    pub async fn write_motor_speed(&self, speed: f32) -> AppResult<()> {
         let tmp = {
             *self.single_circle_pulse.lock().await
         };
         let value: u32 = (tmp as f32 * speed / 60_f32).ceil() as u32;
-        println!("value: {}", value);
-        match self.talk_with_motor(2, value, Duration::from_millis(1000)).await {
-            Ok(_) => { Ok(format!("设置速度为{}RPM成功!", speed)) }
-            _ => { Err("电机控制器响应错误，请检查线路！".into()) }
-        }
+        debug!("value: {}", value);
+        self.talk_with_motor(2, value, self.motor_timeout().await).await?;
+        Ok(())
     }
 
-    pub async fn set_motor_calibrated(&self) -> Result<String, String> {
-        match self.talk_with_motor(3, 0, Duration::from_millis(1000)).await {
-            Ok(_) => { Ok("设置原点位置成功!".into()) }
-            _ => { Err("电机控制器响应错误，请检查线路！".into()) }
+    /// 连接后把设置中的转速写入控制器，使界面和控制器一致，失败只提示不影响连接
+    pub async fn restore_motor_speed(&self) {
+        let speed = self.settings.lock().await.motor.speed;
+        if let Err(e) = self.write_motor_speed(speed).await {
+            warn!("Failed to restore motor speed: {}", e);
+            self.notify("warning", "电机参数", format!("写入转速{}RPM失败: {}", speed, e));
         }
     }
 
-    pub async fn get_motor_angle(&self) -> Result<f32, String> {
-        match self.talk_with_motor(4, 0, Duration::from_millis(1000)).await {
-            Ok(angle) => { Ok(f32::from_bits(angle)) }
-            _ => { Err("电机控制器响应错误，请检查线路！".into()) }
-        }
+    pub async fn set_motor_calibrated(&self) -> AppResult<String> {
+        self.talk_with_motor(3, 0, self.motor_timeout().await).await?;
+        Ok("设置原点位置成功!".into())
     }
 
-    pub async fn motor_start_work(&self) -> Result<String, String> {
-        match self.talk_with_motor(5, 0, Duration::from_millis(1000)).await {
-            Ok(_) => { Ok("开始检测！".into()) }
-            _ => { Err("电机控制器响应错误，请检查线路！".into()) }
+    pub async fn get_motor_angle(&self) -> AppResult<f32> {
+        let angle = f32::from_bits(self.talk_with_motor(4, 0, self.motor_timeout().await).await?);
+        if let Err(e) = self.app_handler.emit("motor_position", MotorPositionEvent { angle }) {
+            warn!("Failed to emit motor position: {}", e);
         }
+        Ok(angle)
     }
 
-    pub async fn motor_stop_work(&self) -> Result<String, String> {
-        match self.talk_with_motor(9, 0, Duration::from_secs(1)).await {
-            Ok(_) => {
-                Ok("停止任务成功！".into())
+    /// 读取控制器中的单步脉冲个数，扩展命令，见 docs/motor_protocol.md
+    pub async fn read_step_pulse(&self) -> AppResult<u32> {
+        self.talk_with_motor(10, 0, self.motor_timeout().await).await
+    }
+
+    /// 读取控制器中的单圈脉冲个数，扩展命令，见 docs/motor_protocol.md
+    pub async fn read_single_circle_pulse(&self) -> AppResult<u32> {
+        self.talk_with_motor(11, 0, self.motor_timeout().await).await
+    }
+
+    /// 读取控制器的脉冲参数并与设置核对，不一致时按设置中的策略处理并提示。
+    /// 固件不支持读取命令时跳过核对，沿用设置值且不写入控制器
+    pub async fn sync_motor_params(&self) -> String {
+        let (stored, policy) = {
+            let settings = self.settings.lock().await;
+            (settings.motor.clone(), settings.pulse_sync)
+        };
+        let read = async {
+            let step_pulse = self.read_step_pulse().await?;
+            let single_circle_pulse = self.read_single_circle_pulse().await?;
+            Ok::<_, AppError>((step_pulse, single_circle_pulse))
+        }
+        .await;
+        let message = match read {
+            Ok((step, single)) if single > 0 && step > 0 && step <= single => {
+                return self.reconcile_motor_params(stored, policy, step, single).await;
             }
-            Err(e) => {
-                Err(e)
+            Ok((step, single)) => {
+                warn!("Invalid pulse parameters from controller: step={} single={}", step, single);
+                "控制器返回的脉冲参数无效，固件可能不支持读取参数命令，跳过同步".to_string()
             }
-        }
+            Err(AppError::Timeout(_)) => "固件不支持读取参数命令，跳过同步".to_string(),
+            Err(e) => format!("无法读取控制器参数，跳过同步: {}", e),
+        };
+        warn!("Motor parameter sync skipped: {}", message);
+        self.apply_motor_params(stored.step_pulse, stored.single_circle_pulse).await;
+        self.notify("warning", "电机参数", message.clone());
+        message
     }
-    pub async fn motor_start_u(&self) -> Result<(), String> {
-        match self.talk_with_motor(6, 0, Duration::from_millis(1000)).await {
-            Ok(_) => { Ok(()) }
-            _ => { Err("电机控制器响应错误，请检查线路！".into()) }
+
+    /// 控制器参数有效时与设置比对，不一致时按策略采用控制器参数或写入设置值
+    async fn reconcile_motor_params(
+        &self,
+        stored: MotorSettings,
+        policy: PulseSyncPolicy,
+        step_pulse: u32,
+        single_circle_pulse: u32,
+    ) -> String {
+        if step_pulse == stored.step_pulse && single_circle_pulse == stored.single_circle_pulse {
+            self.apply_motor_params(step_pulse, single_circle_pulse).await;
+            return "控制器参数与设置一致".into();
         }
+        let message = if policy == PulseSyncPolicy::Controller {
+            self.apply_motor_params(step_pulse, single_circle_pulse).await;
+            self.remember_settings(|s| {
+                s.motor.step_pulse = step_pulse;
+                s.motor.single_circle_pulse = single_circle_pulse;
+            })
+            .await;
+            format!(
+                "控制器参数(单步{}，单圈{})与设置(单步{}，单圈{})不一致，已采用控制器参数",
+                step_pulse, single_circle_pulse, stored.step_pulse, stored.single_circle_pulse
+            )
+        } else {
+            let write = async {
+                self.talk_with_motor(1, stored.single_circle_pulse, self.motor_timeout().await).await?;
+                self.talk_with_motor(0, stored.step_pulse, self.motor_timeout().await).await?;
+                Ok::<_, AppError>(())
+            }
+            .await;
+            self.apply_motor_params(stored.step_pulse, stored.single_circle_pulse).await;
+            match write {
+                Ok(_) => format!(
+                    "控制器参数(单步{}，单圈{})与设置不一致，已写入设置值(单步{}，单圈{})",
+                    step_pulse, single_circle_pulse, stored.step_pulse, stored.single_circle_pulse
+                ),
+                Err(e) => format!("控制器参数与设置不一致，写入设置值失败: {}", e),
+            }
+        };
+        self.notify("warning", "电机参数", message.clone());
+        message
     }
-    pub async fn motor_start_d(&self) -> Result<(), String> {
-        match self.talk_with_motor(7, 0, Duration::from_millis(1000)).await {
-            Ok(_) => { Ok(()) }
-            _ => { Err("电机控制器响应错误，请检查线路！".into()) }
-        }
+
+    async fn apply_motor_params(&self, step_pulse: u32, single_circle_pulse: u32) {
+        *self.step_pulse.lock().await = step_pulse;
+        *self.single_circle_pulse.lock().await = single_circle_pulse;
     }
 
-    pub async fn motor_stop(&self) -> Result<(), String> {
-        match self.talk_with_motor(8, 0, Duration::from_millis(1000)).await {
-            Ok(_) => { Ok(()) }
-            _ => { Err("电机控制器响应错误，请检查线路！".into()) }
-        }
+    pub async fn motor_start_work(&self) -> AppResult<String> {
+        safety::ensure_motion_allowed(self).await?;
+        self.talk_with_motor(REPORT_COMMAND, 0, self.motor_timeout().await).await?;
+        Ok("开始检测！".into())
     }
 
-    pub async fn get_laser_data(&self) -> Result<BTreeMap<u8, Vec<u8>>, String> {
+    pub async fn motor_stop_work(&self) -> AppResult<String> {
+        self.talk_with_motor(9, 0, self.motor_timeout().await).await?;
+        Ok("停止任务成功！".into())
+    }
+    pub async fn motor_start_u(&self) -> AppResult<()> {
+        safety::ensure_motion_allowed(self).await?;
+        self.talk_with_motor(6, 0, self.motor_timeout().await).await?;
+        Ok(())
+    }
+    pub async fn motor_start_d(&self) -> AppResult<()> {
+        safety::ensure_motion_allowed(self).await?;
+        self.talk_with_motor(7, 0, self.motor_timeout().await).await?;
+        Ok(())
+    }
+
+    pub async fn motor_stop(&self) -> AppResult<()> {
+        self.talk_with_motor(8, 0, self.motor_timeout().await).await?;
+        Ok(())
+    }
+
+    pub async fn get_laser_data(&self) -> AppResult<BTreeMap<u8, Vec<u8>>> {
         let mut lock = self.laser_socket.lock().await;
         let socket = match lock.as_mut() {
             Some(a) => a, // 这里 clone 一个 String
-            None => return Err("Laser socket not initialized".into()),
+            None => return Err(AppError::Disconnected(Device::Laser)),
         };
-        let mut lese = [0u8; 2048];
-        while let Ok(size) = socket.try_recv(&mut lese) {
-            if size == 0 {
-                break; //清空接收缓冲区
-            }
-        }
-        let get_data_pkg: [u8; 8] = [0xAA, 0x55, 0x55, 0xAA, 0x02, 0x00, 0x21, 0x01];
-        socket
-            .send(&get_data_pkg)
-            .await
-            .map_err(|e| e.to_string())?;
+        self.send_laser_query(socket).await?;
         let mut frames = BTreeMap::new();
         while frames.len() < 8 {
             let mut buf = [0u8; 2048];
             let len = timeout(Duration::from_millis(200), socket.recv(&mut buf))
                 .await
-                .map_err(|_| "Timeout while receiving frame")?
-                .map_err(|e| e.to_string())?;
+                .map_err(|_| AppError::Timeout(Device::Laser))?
+                .map_err(|e| AppError::io(Device::Laser, e))?;
+            self.capture.record(Device::Laser, Direction::Rx, &buf[..len]);
 
             if len < 1 {
-                return Err("Received empty frame".into());
+                return Err(AppError::protocol(Device::Laser, "收到空帧"));
             }
 
             let frame_id = buf[len - 1]; // 最后一个字节是帧号
             frames.insert(frame_id, buf[..len - 1].to_vec()); // 去掉帧号保存
-            println!("Frame ID: {}", frame_id);
+            trace!("Frame ID: {}", frame_id);
         }
         Ok(frames)
     }
+
+    /// 清空接收缓冲区后发送一次取数请求
+    async fn send_laser_query(&self, socket: &UdpSocket) -> AppResult<()> {
+        let mut lese = [0u8; 2048];
+        while let Ok(size) = socket.try_recv(&mut lese) {
+            if size == 0 {
+                break; //清空接收缓冲区
+            }
+        }
+        let get_data_pkg: [u8; 8] = [0xAA, 0x55, 0x55, 0xAA, 0x02, 0x00, 0x21, 0x01];
+        socket
+            .send(&get_data_pkg)
+            .await
+            .map_err(|e| AppError::io(Device::Laser, e))?;
+        self.capture.record(Device::Laser, Direction::Tx, &get_data_pkg);
+        Ok(())
+    }
+
+    /// 链路检测只等待第一帧应答，不做完整采集，其余帧由下次取数前清空
+    pub async fn ping_laser(&self) -> AppResult<()> {
+        let lock = self.laser_socket.lock().await;
+        let socket = lock.as_ref().ok_or(AppError::Disconnected(Device::Laser))?;
+        self.send_laser_query(socket).await?;
+        let mut buf = [0u8; 2048];
+        let len = timeout(Duration::from_millis(200), socket.recv(&mut buf))
+            .await
+            .map_err(|_| AppError::Timeout(Device::Laser))?
+            .map_err(|e| AppError::io(Device::Laser, e))?;
+        self.capture.record(Device::Laser, Direction::Rx, &buf[..len]);
+        Ok(())
+    }
     pub async fn push_hall_data(&self, payload: Payload) {
-        let mut buf = self.hall_buffer.lock().await;
-        if buf.len() >= BUFFER_SIZE {
-            buf.pop_front(); // 丢弃最旧的
+        self.hall_buffer.lock().await.push(payload);
+    }
+
+    /// 按读者各自的进度读取，读取不会影响其他读者
+    pub async fn fetch_hall_data(&self, consumer: &str, max: usize) -> Vec<Sequenced<Payload>> {
+        let slice = self.hall_buffer.lock().await.read(consumer, max);
+        self.report_hall_overflow(consumer, &slice);
+        slice.items
+    }
+
+    /// 读取序号不小于 since 的数据
+    pub async fn fetch_hall_since(&self, since: u64, max: usize) -> RingSlice<Payload> {
+        let slice = self.hall_buffer.lock().await.since(since, max);
+        self.report_hall_overflow("since", &slice);
+        slice
+    }
+
+    /// 从最新数据向前取，直到累计转过一圈
+    pub async fn hall_last_revolution(&self) -> Vec<Sequenced<Payload>> {
+        let buf = self.hall_buffer.lock().await;
+        let mut last: Option<f32> = None;
+        let mut travelled = 0.0;
+        buf.latest_while(|p| {
+            if let Some(last) = last {
+                // 向前回溯，角度差取模后累加
+                travelled += (last - p.angle).rem_euclid(360.0);
+            }
+            last = Some(p.angle);
+            travelled < 360.0
+        })
+    }
+
+    /// 保存一个角度的轮廓，返回其序号
+    pub async fn push_laser_profile(&self, angle: f32, time: i64, points: Vec<LaserData>) -> u64 {
+        self.laser_buffer.lock().await.push(LaserProfile { angle, time, points })
+    }
+
+    /// 按序号取轮廓，未指定序号时取最新的，已被覆盖时为空
+    pub async fn laser_profile(&self, seq: Option<u64>) -> Option<Sequenced<LaserProfile>> {
+        let buf = self.laser_buffer.lock().await;
+        match seq {
+            Some(seq) => buf.get(seq),
+            None => buf.latest(),
         }
-        buf.push_back(payload);
     }
 
-    pub async fn fetch_hall_data(&self, max: usize) -> Vec<Payload> {
-        let mut buf = self.hall_buffer.lock().await;
-        let mut res = Vec::new();
-        for _ in 0..max {
-            if let Some(item) = buf.pop_front() {
-                res.push(item);
-            } else {
-                break;
+    /// 角度范围内每个角度最新的轮廓，按角度排序，start 大于 end 时表示跨过 0°，相差 360° 及以上时为整圈
+    pub async fn laser_profiles_in_range(&self, start: f32, end: f32) -> Vec<Sequenced<LaserProfile>> {
+        let buf = self.laser_buffer.lock().await;
+        // 以千分之一度为键去重，多圈采集时保留最新的一次
+        let mut profiles: BTreeMap<i64, Sequenced<LaserProfile>> = BTreeMap::new();
+        for p in buf.iter_latest() {
+            let angle = p.item.angle.rem_euclid(360.0);
+            if angle_in_range(start, end, angle) {
+                profiles.entry((angle * 1000.0).round() as i64).or_insert_with(|| p.clone());
             }
         }
-        res
+        profiles.into_values().collect()
+    }
+
+    fn report_hall_overflow(&self, consumer: &str, slice: &RingSlice<Payload>) {
+        if slice.reset {
+            warn!("Hall buffer sequence reset for consumer {}", consumer);
+        }
+        if slice.lost > 0 {
+            warn!("Hall buffer overflow, consumer {} lost {} items", consumer, slice.lost);
+            self.notify("warning", "霍尔数据丢失", format!("读取过慢，缓冲区已覆盖{}条数据", slice.lost));
+        }
     }
 }
-#[derive(Clone, serde::Serialize)]
+#[derive(Clone, PartialEq, serde::Serialize)]
 pub struct PortInfo {
     pub port: String,
+    /// 供界面显示的描述
     pub info: String,
+    /// usb / pci / bluetooth / unknown
+    pub port_type: String,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    pub serial_number: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub interface: Option<u8>,
+    /// 按记住的设备标识识别出的设备
+    pub device: Option<Device>,
 }
 #[derive(Clone, serde::Serialize)]
 pub struct Payload {
     angle: f32,
     data: Vec<i32>,
+    std: Vec<f32>,
 }
 const BUFFER_SIZE: usize = 10000; // 环形缓冲区大小
+const MOTOR_CHANNEL_SIZE: usize = 32;
+
+/// 单个角度的激光轮廓
+#[derive(Clone, serde::Serialize)]
+pub struct LaserProfile {
+    angle: f32,
+    /// 采集时间，毫秒时间戳
+    time: i64,
+    points: Vec<LaserData>,
+}
+const LASER_BUFFER_SIZE: usize = 720; // 轮廓数据较大，只保留最近若干个角度
 
 #[derive(Clone, serde::Serialize)]
 struct SerialPortList {
@@ -451,39 +837,75 @@ struct SerialPortList {
 pub async fn run() {
     // 创建 stop channel
     let (stop_tx, _stop_rx) = watch::channel(false);
-    let (tx, rx) = mpsc::channel(32);
+    let (motor_events, _) = broadcast::channel(EVENT_CHANNEL_SIZE);
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .setup(|app| {
 
+            let log_dir = logging::log_dir(app.handle()).ok();
+            logging::init(log_dir.as_deref());
+            let settings_path = settings_path(app.handle())?;
+            let settings = AppSettings::load(&settings_path);
+            logging::apply(&settings.log)?;
             // 初始化 AppWrapper
             let app_wrapper = AppWrapper {
                 app_handler: app.handle().clone(),
-                step_pulse: 40.into(),
+                step_pulse: settings.motor.step_pulse.into(),
                 hall_serial: Default::default(),
-                motor_serial: Default::default(),
+                motor: Default::default(),
+                motor_events,
                 laser_address: Default::default(),
                 laser_socket: Default::default(),
-                single_circle_pulse: 15000.into(),
+                single_circle_pulse: settings.motor.single_circle_pulse.into(),
                 stop_tx,
-                hall_buffer: Mutex::new(VecDeque::with_capacity(BUFFER_SIZE)),
-                motor_tx: tx,
-                motor_rx: Mutex::new(rx),
+                hall_buffer: Mutex::new(SeqRing::new(BUFFER_SIZE)),
+                laser_buffer: Mutex::new(SeqRing::new(LASER_BUFFER_SIZE)),
+                hall_target: Default::default(),
+                motor_target: Default::default(),
+                scanning: AtomicBool::new(false),
+                moving: AtomicBool::new(false),
+                probe_lock: Mutex::new(()),
+                settings: Mutex::new(settings),
+                settings_path,
+                interlock: Default::default(),
+                capture: Default::default(),
             };
 
-            connect_to_db().expect("Failed to connect to DB");
+            init_db()?;
 
             // 注入到 Tauri state
-            app.manage(Arc::new(app_wrapper));
+            let app_wrapper = Arc::new(app_wrapper);
+            app.manage(app_wrapper.clone());
+            spawn_port_monitor(app_wrapper.clone());
+            spawn_interlock_monitor(app_wrapper.clone());
+            spawn_health_monitor(app_wrapper);
 
             Ok(())
         })
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             get_data_by_parent_id,
+            get_stat_by_parent_id,
             gen_xlsx,
+            get_project_verdict,
             get_port,
             init_device,
+            connect_hall,
+            connect_motor,
+            connect_laser,
+            disconnect_hall,
+            disconnect_motor,
+            disconnect_laser,
+            get_device_status,
+            get_settings,
+            home_motor,
+            move_to_angle,
+            set_motion_profile,
+            apply_motion_profile,
+            set_settings,
+            sync_motor_params,
+            probe_ports,
+            discover_lasers,
             get_hall,
             get_laser,
             rotate_motor,
@@ -494,8 +916,21 @@ pub async fn run() {
             motor_start_d,
             motor_stop,
             start_work,
+            start_replay,
+            start_capture,
+            stop_capture,
+            decode_capture,
+            set_log_level,
+            fetch_logs,
             stop_work,
+            emergency_stop,
+            get_interlock,
+            acknowledge_fault,
             fetch_hall_data,
+            fetch_hall_since,
+            hall_last_revolution,
+            get_laser_profile,
+            fetch_laser_profiles,
             motor_start_one_circle,
             set_motor_single_circle_pulse,
             get_motor_angle,
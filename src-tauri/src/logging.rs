@@ -0,0 +1,287 @@
+use crate::error::{AppError, AppResult, Device};
+use crate::settings::LogSettings;
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock, RwLock};
+use tauri::{AppHandle, Manager};
+
+/// 本 crate 的日志 target 前缀，配置模块级别时可省略
+const CRATE_PREFIX: &str = concat!(env!("CARGO_CRATE_NAME"), "::");
+const LOG_FILE: &str = "app.log";
+/// 单个日志文件的大小上限，超过后轮转
+const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+/// 除当前文件外保留的历史文件数
+const KEEP_FILES: usize = 4;
+/// 内存中保留供界面查看的最近日志行数
+const RECENT_LINES: usize = 2000;
+
+/// 一行日志
+#[derive(Clone, Serialize)]
+pub struct LogLine {
+    pub time: String,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+struct Levels {
+    default: LevelFilter,
+    modules: BTreeMap<String, LevelFilter>,
+}
+
+impl Levels {
+    /// 取最长匹配的模块级别，没有配置时使用默认级别
+    fn level_for(&self, target: &str) -> LevelFilter {
+        let target = target.strip_prefix(CRATE_PREFIX).unwrap_or(target);
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target == module.as_str()
+                    || (target.starts_with(module.as_str()) && target[module.len()..].starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    fn max(&self) -> LevelFilter {
+        self.modules.values().copied().fold(self.default, Ord::max)
+    }
+}
+
+struct LogFile {
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(dir: &Path) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let file = OpenOptions::new().create(true).append(true).open(dir.join(LOG_FILE))?;
+        let size = file.metadata()?.len();
+        Ok(LogFile { file, size })
+    }
+}
+
+/// app.log 依次改名为 app.log.1、app.log.2 …，超出保留数量的直接覆盖，调用前需关闭当前文件
+fn rotate(dir: &Path) -> std::io::Result<()> {
+    let name = |i: usize| dir.join(format!("{}.{}", LOG_FILE, i));
+    for i in (1..KEEP_FILES).rev() {
+        let from = name(i);
+        if from.exists() {
+            std::fs::rename(from, name(i + 1))?;
+        }
+    }
+    std::fs::rename(dir.join(LOG_FILE), name(1))
+}
+
+struct Logger {
+    levels: RwLock<Levels>,
+    dir: Option<PathBuf>,
+    file: Mutex<Option<LogFile>>,
+    recent: Mutex<VecDeque<LogLine>>,
+}
+
+impl Logger {
+    fn write_file(&self, text: &str) -> std::io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let (Some(f), Some(dir)) = (file.as_mut(), &self.dir) else { return Ok(()) };
+        if f.size + text.len() as u64 > MAX_FILE_SIZE {
+            // 先关闭当前文件，Windows 下无法重命名已打开的文件
+            *file = None;
+            rotate(dir)?;
+            *file = Some(LogFile::open(dir)?);
+        }
+        let Some(f) = file.as_mut() else { return Ok(()) };
+        f.file.write_all(text.as_bytes())?;
+        f.size += text.len() as u64;
+        Ok(())
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let levels = self.levels.read().unwrap_or_else(|e| e.into_inner());
+        metadata.level() <= levels.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = LogLine {
+            time: Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+            level: record.level().to_string(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        };
+        let text = format!("{} {:<5} {}: {}\n", line.time, line.level, line.target, line.message);
+        // 开发时仍输出到控制台
+        eprint!("{}", text);
+        if let Err(e) = self.write_file(&text) {
+            // 日志文件不可写时只保留控制台和内存中的日志
+            eprintln!("Failed to write log file, disabled: {}", e);
+            *self.file.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        if recent.len() >= RECENT_LINES {
+            recent.pop_front();
+        }
+        recent.push_back(line);
+    }
+
+    fn flush(&self) {
+        if let Some(f) = self.file.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            let _ = f.file.flush();
+        }
+    }
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+fn parse_level(level: &str) -> AppResult<LevelFilter> {
+    LevelFilter::from_str(level).map_err(|_| AppError::InvalidInput(format!("日志级别{}不合法", level)))
+}
+
+fn parse_levels(settings: &LogSettings) -> AppResult<Levels> {
+    let mut modules = BTreeMap::new();
+    for (module, level) in &settings.modules {
+        let module = module.strip_prefix(CRATE_PREFIX).unwrap_or(module);
+        modules.insert(module.to_string(), parse_level(level)?);
+    }
+    Ok(Levels {
+        default: parse_level(&settings.level)?,
+        modules,
+    })
+}
+
+/// 安装日志记录器并打开日志目录下的日志文件，文件打开失败时只输出到控制台
+///
+/// 读取设置前先按 info 级别记录，读取后再调用 apply。没有使用 tauri-plugin-log：
+/// 它只在启动打开文件时检查大小，长时间运行不会轮转，级别也在构建时固定，运行中修改仍需自己过滤。
+pub fn init(dir: Option<&Path>) {
+    let file = dir.and_then(|dir| match LogFile::open(dir) {
+        Ok(f) => Some(f),
+        Err(e) => {
+            eprintln!("Failed to open log file in {}: {}", dir.display(), e);
+            None
+        }
+    });
+    let logger = LOGGER.get_or_init(|| Logger {
+        levels: RwLock::new(Levels {
+            default: LevelFilter::Info,
+            modules: BTreeMap::new(),
+        }),
+        dir: dir.map(Path::to_path_buf),
+        file: Mutex::new(file),
+        recent: Mutex::new(VecDeque::with_capacity(RECENT_LINES)),
+    });
+    match log::set_logger(logger) {
+        Ok(_) => log::set_max_level(LevelFilter::Info),
+        Err(e) => eprintln!("Failed to install logger: {}", e),
+    }
+}
+
+/// 运行时修改日志级别
+pub fn apply(settings: &LogSettings) -> AppResult<()> {
+    let levels = parse_levels(settings)?;
+    let logger = LOGGER
+        .get()
+        .ok_or_else(|| AppError::State("日志尚未初始化".into()))?;
+    log::set_max_level(levels.max());
+    *logger.levels.write().unwrap_or_else(|e| e.into_inner()) = levels;
+    Ok(())
+}
+
+/// 最近的日志，按时间顺序返回不低于 min_level 的最多 max 行
+pub fn recent(max: usize, min_level: Option<&str>) -> AppResult<Vec<LogLine>> {
+    let min_level = match min_level {
+        Some(level) => parse_level(level)?,
+        None => LevelFilter::Trace,
+    };
+    let Some(logger) = LOGGER.get() else { return Ok(Vec::new()) };
+    let recent = logger.recent.lock().unwrap_or_else(|e| e.into_inner());
+    let mut lines: Vec<LogLine> = recent
+        .iter()
+        .rev()
+        .filter(|line| line.level.parse::<LevelFilter>().map(|l| l <= min_level).unwrap_or(true))
+        .take(max)
+        .cloned()
+        .collect();
+    lines.reverse();
+    Ok(lines)
+}
+
+/// 日志文件所在目录
+pub fn log_dir(app: &AppHandle) -> AppResult<PathBuf> {
+    app.path().app_log_dir().map_err(|e| AppError::io(Device::File, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(default: LevelFilter, modules: &[(&str, LevelFilter)]) -> Levels {
+        Levels {
+            default,
+            modules: modules.iter().map(|(m, l)| (m.to_string(), *l)).collect(),
+        }
+    }
+
+    #[test]
+    fn level_for_uses_longest_module() {
+        let levels = levels(
+            LevelFilter::Info,
+            &[("motor", LevelFilter::Debug), ("motor::link", LevelFilter::Trace), ("tao", LevelFilter::Warn)],
+        );
+        assert_eq!(levels.level_for("device"), LevelFilter::Info);
+        assert_eq!(levels.level_for("motor"), LevelFilter::Debug);
+        assert_eq!(levels.level_for("motor::other"), LevelFilter::Debug);
+        assert_eq!(levels.level_for("motor::link::frame"), LevelFilter::Trace);
+        assert_eq!(levels.level_for("tao::platform"), LevelFilter::Warn);
+        // 只按完整的模块名匹配
+        assert_eq!(levels.level_for("motorx"), LevelFilter::Info);
+        // 本 crate 的模块可以省略 crate 名
+        assert_eq!(levels.level_for(&format!("{}motor", CRATE_PREFIX)), LevelFilter::Debug);
+        assert_eq!(levels.max(), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn parse_levels_strips_crate_prefix() {
+        let mut settings = LogSettings {
+            level: "warn".into(),
+            modules: BTreeMap::from([(format!("{}device", CRATE_PREFIX), "debug".into())]),
+        };
+        let levels = parse_levels(&settings).unwrap();
+        assert_eq!(levels.default, LevelFilter::Warn);
+        assert_eq!(levels.level_for("device"), LevelFilter::Debug);
+        settings.level = "loud".into();
+        assert!(parse_levels(&settings).is_err());
+    }
+
+    #[test]
+    fn rotate_shifts_files_and_drops_oldest() {
+        let dir = std::env::temp_dir().join(format!("log-rotate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let name = |i: usize| dir.join(format!("{}.{}", LOG_FILE, i));
+        std::fs::write(dir.join(LOG_FILE), "current").unwrap();
+        for i in 1..=KEEP_FILES {
+            std::fs::write(name(i), i.to_string()).unwrap();
+        }
+        rotate(&dir).unwrap();
+        let read = |i: usize| std::fs::read_to_string(name(i)).unwrap();
+        assert!(!dir.join(LOG_FILE).exists());
+        assert_eq!(read(1), "current");
+        for i in 2..=KEEP_FILES {
+            assert_eq!(read(i), (i - 1).to_string());
+        }
+        assert!(!name(KEEP_FILES + 1).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::device;
+use crate::error::{AppError, AppResult, Device};
+use crate::serial::laser_parse_data;
+use crate::settings::{HomingDirection, HomingSettings, HomingSource, MotionProfile};
+use crate::AppWrapper;
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// 读取回零参考信号，霍尔取指定通道读数，激光取轮廓平均距离
+async fn read_reference(app: &AppWrapper, homing: &HomingSettings, laser_d: f32) -> AppResult<f32> {
+    match homing.source {
+        HomingSource::Hall => {
+            let data = app.get_hall_data().await?;
+            data.get(homing.channel)
+                .map(|v| *v as f32)
+                .ok_or_else(|| AppError::InvalidInput(format!("霍尔通道{}不存在", homing.channel + 1)))
+        }
+        HomingSource::Laser => {
+            let frames = app.get_laser_data().await?;
+            let points = laser_parse_data(frames, 0.0, laser_d)
+                .ok_or_else(|| AppError::parse(Device::Laser, "轮廓数据长度错误"))?;
+            if points.is_empty() {
+                return Err(AppError::parse(Device::Laser, "轮廓中没有有效点"));
+            }
+            // 角度取 0 时 x 即为距离
+            Ok(points.iter().map(|p| p.x).sum::<f32>() / points.len() as f32)
+        }
+    }
+}
+
+/// 按方向点动直到参考读数满足条件，满足后再继续转动 hold 时长，无论结果如何都停止点动
+async fn jog_until(
+    app: &AppWrapper,
+    homing: &HomingSettings,
+    laser_d: f32,
+    direction: HomingDirection,
+    hold: Duration,
+    until: impl Fn(f32) -> bool,
+) -> AppResult<f32> {
+    let result = async {
+        match direction {
+            HomingDirection::Up => app.motor_start_u().await?,
+            HomingDirection::Down => app.motor_start_d().await?,
+        }
+        let deadline = Instant::now() + Duration::from_secs(homing.timeout_s);
+        let mut ticker = tokio::time::interval(Duration::from_millis(homing.poll_ms));
+        loop {
+            ticker.tick().await;
+            let value = read_reference(app, homing, laser_d).await?;
+            if until(value) {
+                tokio::time::sleep(hold).await;
+                return Ok(value);
+            }
+            if Instant::now() >= deadline {
+                return Err(AppError::State(format!("回零超时，{}秒内未检测到参考特征", homing.timeout_s)));
+            }
+        }
+    }
+    .await;
+    let stopped = app.motor_stop().await;
+    let value = result?;
+    stopped?;
+    Ok(value)
+}
+
+/// 快速逼近参考特征，反向回退到信号消失后再退一段，然后以慢速再次逼近，
+/// 使原点位置不受逼近速度和停止过冲的影响
+async fn seek_reference(app: &AppWrapper, homing: &HomingSettings, laser_d: f32) -> AppResult<f32> {
+    let baseline = read_reference(app, homing, laser_d).await?;
+    let detected = |value: f32| (value - baseline).abs() >= homing.threshold;
+    jog_until(app, homing, laser_d, homing.direction, Duration::ZERO, detected).await?;
+    let back_off = Duration::from_millis(homing.back_off_ms);
+    jog_until(app, homing, laser_d, homing.direction.reverse(), back_off, |v| !detected(v)).await?;
+    app.write_motor_speed(homing.slow_speed).await?;
+    jog_until(app, homing, laser_d, homing.direction, Duration::ZERO, detected).await
+}
+
+/// 占用电机进行运动，采集进行中或已有运动时返回错误
+async fn claim_motion(app: &AppWrapper, action: &str) -> AppResult<()> {
+    if !device::claim(app, &app.moving).await {
+        return Err(AppError::State(format!("电机运动中，无法{}", action)));
+    }
+    // 先置位再检查采集标志，与开始采集的顺序相反，两者不会同时成立
+    if app.scanning.load(Ordering::SeqCst) {
+        app.moving.store(false, Ordering::SeqCst);
+        return Err(AppError::State(format!("采集进行中，无法{}", action)));
+    }
+    Ok(())
+}
+
+/// 自动回零：快速逼近、回退后慢速逼近参考特征，停止后将当前位置设为原点，最后恢复设置中的转速
+pub async fn home_motor(app: &AppWrapper) -> AppResult<String> {
+    claim_motion(app, "回零").await?;
+    let (homing, laser_d, speed) = {
+        let settings = app.settings.lock().await;
+        (settings.homing.clone(), settings.laser_d, settings.motor.speed)
+    };
+    let result = async {
+        let value = seek_reference(app, &homing, laser_d).await?;
+        app.set_motor_calibrated().await?;
+        Ok(format!("回零完成，参考读数{:.2}", value))
+    }
+    .await;
+    // 慢速逼近改过转速，无论是否成功都要恢复
+    let restored = app.write_motor_speed(speed).await;
+    app.moving.store(false, Ordering::SeqCst);
+    let message = result?;
+    restored?;
+    Ok(message)
+}
+
+/// 绝对定位的结果
+#[derive(Clone, Serialize)]
+pub struct MoveResult {
+    pub target: f32,
+    pub angle: f32,
+    /// 最终角度与目标的偏差，单位度
+    pub error: f32,
+    /// 实际下发的转动次数
+    pub attempts: u32,
+}
+
+/// 反转点动时回读角度的间隔
+const REVERSE_POLL: Duration = Duration::from_millis(20);
+/// 反转点动的最长时间，与按脉冲转动的等待时间一致
+const REVERSE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// 从 from 转到 to 的最短带符号角度，范围 (-180, 180]
+fn shortest_delta(from: f32, to: f32) -> f32 {
+    let delta = (to - from).rem_euclid(360.0);
+    if delta > 180.0 {
+        delta - 360.0
+    } else {
+        delta
+    }
+}
+
+/// 角度是否在范围内，start 大于 end 时表示跨过 0°，end 比 start 大 360° 及以上时为整圈
+pub fn angle_in_range(start: f32, end: f32, angle: f32) -> bool {
+    if end - start >= 360.0 {
+        return true;
+    }
+    let (start, end, angle) = (start.rem_euclid(360.0), end.rem_euclid(360.0), angle.rem_euclid(360.0));
+    if start <= end {
+        (start..=end).contains(&angle)
+    } else {
+        angle >= start || angle <= end
+    }
+}
+
+/// 定位的下一步
+#[derive(Debug, PartialEq)]
+enum Step {
+    /// 偏差在容差内或不足一个脉冲
+    Done,
+    /// 按脉冲数正转（命令 1）
+    Forward(u32),
+    /// 目标在后方，需要反转
+    Reverse,
+}
+
+/// 按最短方向决定下一步，过冲在容差内时视为到位，不再转将近一圈回来
+fn plan_step(angle: f32, target: f32, tolerance: f32, single_circle_pulse: u32) -> Step {
+    let delta = shortest_delta(angle, target);
+    let pulse = (delta.abs() * single_circle_pulse as f32 / 360.0).round() as u32;
+    if delta.abs() <= tolerance || pulse == 0 {
+        Step::Done
+    } else if delta > 0.0 {
+        Step::Forward(pulse)
+    } else {
+        Step::Reverse
+    }
+}
+
+/// 反转到目标：控制器没有反向的按脉冲转动命令，用手动反转（命令 7）点动并回读角度，
+/// 到达或越过目标后停止（命令 8），停止时的过冲由下一次正转修正
+async fn reverse_to(app: &AppWrapper, target: f32, tolerance: f32) -> AppResult<()> {
+    let result = async {
+        app.motor_start_d().await?;
+        let deadline = Instant::now() + REVERSE_TIMEOUT;
+        let mut ticker = tokio::time::interval(REVERSE_POLL);
+        loop {
+            ticker.tick().await;
+            if shortest_delta(app.get_motor_angle().await?, target) >= -tolerance {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(AppError::Timeout(Device::Motor));
+            }
+        }
+    }
+    .await;
+    let stopped = app.motor_stop().await;
+    result?;
+    stopped
+}
+
+async fn seek_angle(app: &AppWrapper, target: f32, tolerance: f32, retries: u32) -> AppResult<MoveResult> {
+    let single_circle_pulse = *app.single_circle_pulse.lock().await;
+    let settle = Duration::from_millis(app.settings.lock().await.motion_profile().settle_ms as u64);
+    let mut attempts = 0;
+    loop {
+        let angle = app.get_motor_angle().await?;
+        let delta = shortest_delta(angle, target);
+        let step = plan_step(angle, target, tolerance, single_circle_pulse);
+        if step == Step::Done || attempts > retries {
+            if delta.abs() > tolerance {
+                return Err(AppError::State(format!(
+                    "定位到{:.3}°失败，当前{:.3}°，偏差{:.3}°超出容差{:.3}°",
+                    target, angle, delta, tolerance
+                )));
+            }
+            return Ok(MoveResult {
+                target,
+                angle,
+                error: delta,
+                attempts,
+            });
+        }
+        match step {
+            Step::Forward(pulse) => app.rotate_motor_pulse(pulse).await?,
+            _ => reverse_to(app, target, tolerance).await?,
+        }
+        // 等待机械稳定后再回读角度
+        tokio::time::sleep(settle).await;
+        attempts += 1;
+    }
+}
+
+/// 按最短方向转到绝对角度，到位后回读角度校验，偏差超出容差时重试
+pub async fn move_to_angle(app: &AppWrapper, target: f32, tolerance: f32, retries: u32) -> AppResult<MoveResult> {
+    if !target.is_finite() {
+        return Err(AppError::InvalidInput("目标角度不合法".into()));
+    }
+    if !tolerance.is_finite() || tolerance <= 0.0 {
+        return Err(AppError::InvalidInput("角度容差必须大于0".into()));
+    }
+    claim_motion(app, "定位").await?;
+    let result = seek_angle(app, target.rem_euclid(360.0), tolerance, retries).await;
+    app.moving.store(false, Ordering::SeqCst);
+    result
+}
+
+/// 应用运动曲线：当前转速超过最大转速时限幅后写入（命令 2），稳定时间由上位机在每次定位后等待。
+/// 加减速等参数需要扩展固件（命令 13~17，见 docs/motor_protocol.md），只在开启 motor_extended_protocol 时写入
+pub async fn apply_motion_profile(app: &AppWrapper, profile: &MotionProfile) -> AppResult<String> {
+    profile.validate()?;
+    let (speed, extended) = {
+        let settings = app.settings.lock().await;
+        (settings.motor.speed.min(profile.max_speed), settings.motor_extended_protocol)
+    };
+    app.write_motor_speed(speed).await?;
+    if !extended {
+        return Ok(format!("转速{}RPM已写入，控制器不支持加减速参数，未写入", speed));
+    }
+    let single_circle_pulse = *app.single_circle_pulse.lock().await as f32;
+    // RPM 换算为每秒脉冲数，与 set_motor_speed 一致
+    let to_pulse = |rpm: f32| (single_circle_pulse * rpm / 60.0).ceil() as u32;
+    app.write_motor_param(13, to_pulse(profile.max_speed)).await?;
+    app.write_motor_param(14, to_pulse(profile.accel)).await?;
+    app.write_motor_param(15, to_pulse(profile.decel)).await?;
+    app.write_motor_param(16, to_pulse(profile.jerk)).await?;
+    app.write_motor_param(17, profile.settle_ms).await?;
+    Ok(format!("转速{}RPM及加减速参数已写入", speed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortest_delta_wraps_around() {
+        assert_eq!(shortest_delta(10.0, 30.0), 20.0);
+        assert_eq!(shortest_delta(350.0, 10.0), 20.0);
+        assert_eq!(shortest_delta(10.0, 350.0), -20.0);
+        assert_eq!(shortest_delta(0.0, 180.0), 180.0);
+    }
+
+    #[test]
+    fn angle_range_handles_wrap_and_full_turn() {
+        assert!(angle_in_range(10.0, 20.0, 15.0));
+        assert!(!angle_in_range(10.0, 20.0, 25.0));
+        assert!(angle_in_range(350.0, 10.0, 5.0));
+        assert!(angle_in_range(350.0, 10.0, 355.0));
+        assert!(!angle_in_range(350.0, 10.0, 180.0));
+        // (0, 360) 是整圈，不能因为 360 取模成 0 而只剩 0°
+        assert!(angle_in_range(0.0, 360.0, 180.0));
+        assert!(angle_in_range(-90.0, 270.0, 100.0));
+    }
+
+    #[test]
+    fn plan_step_takes_shortest_direction() {
+        assert_eq!(plan_step(0.0, 90.0, 0.1, 3600), Step::Forward(900));
+        assert_eq!(plan_step(350.0, 10.0, 0.1, 3600), Step::Forward(200));
+        // 目标在后方时反转，不再正转将近一圈
+        assert_eq!(plan_step(10.0, 350.0, 0.1, 3600), Step::Reverse);
+        // 容差内的过冲视为到位
+        assert_eq!(plan_step(90.05, 90.0, 0.1, 3600), Step::Done);
+        assert_eq!(plan_step(0.0, 359.99, 0.1, 3600), Step::Done);
+        // 不足一个脉冲时也结束
+        assert_eq!(plan_step(0.0, 0.04, 0.01, 3600), Step::Done);
+    }
+}
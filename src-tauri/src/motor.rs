@@ -0,0 +1,459 @@
+use crate::capture::{Capture, Direction};
+use crate::error::{AppError, AppResult, Device};
+use crate::safety::CMD_INTERLOCK;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use log::{error, warn};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::time::Instant;
+use tokio_util::bytes::{Buf, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+const QUEUE_SIZE: usize = 32;
+const FRAME_LEN: usize = 9;
+const FRAME_HEAD: [u8; 2] = [0xEF, 0xFE];
+const FRAME_TAIL: [u8; 2] = [0xFF, 0xEE];
+pub const EVENT_CHANNEL_SIZE: usize = 256;
+/// 开始检测命令号，检测过程中控制器用同一命令号上报角度
+pub const REPORT_COMMAND: u8 = 5;
+
+/// 控制器主动上报的数据，以及链路关闭通知
+#[derive(Clone, Debug)]
+pub enum MotorEvent {
+    /// 采集过程中每到一个角度上报的帧
+    Angle(f32),
+    /// 控制器报告检测结束
+    Finished,
+    Interlock(u32),
+    Closed(AppError),
+}
+
+struct MotorRequest {
+    command: u8,
+    value: u32,
+    timeout: Duration,
+    reply: oneshot::Sender<AppResult<BytesMut>>,
+}
+
+struct InFlight {
+    command: u8,
+    deadline: Instant,
+    reply: oneshot::Sender<AppResult<BytesMut>>,
+}
+
+/// 电机链路句柄，串口由后台任务独占，句柄全部释放后任务退出并关闭串口
+#[derive(Clone)]
+pub struct MotorLink {
+    cmd_tx: mpsc::Sender<MotorRequest>,
+    estop_tx: mpsc::Sender<oneshot::Sender<AppResult<()>>>,
+}
+
+pub fn encode_frame(command: u8, value: u32) -> Bytes {
+    let mut pkg: [u8; 9] = [0xEF, 0xFE, command, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xEE];
+    pkg[3..7].copy_from_slice(&value.to_le_bytes());
+    Bytes::copy_from_slice(&pkg[..])
+}
+
+/// 校验电机控制器的 9 字节响应帧，返回其中的 4 字节数值
+pub fn parse_frame(data: &[u8]) -> AppResult<u32> {
+    if data.len() != FRAME_LEN {
+        return Err(AppError::protocol(Device::Motor, format!("帧长度为{}字节", data.len())));
+    }
+    if data[0..2] != FRAME_HEAD || data[7..] != FRAME_TAIL {
+        return Err(AppError::protocol(Device::Motor, "帧头或帧尾错误"));
+    }
+    Ok(u32::from_le_bytes([data[3], data[4], data[5], data[6]]))
+}
+
+/// 电机串口的分帧：串口读到的数据块可能只有半帧，也可能连着几帧，
+/// 累积到缓冲区后按帧头 `EF FE` 重新同步，帧尾 `FF EE` 校验通过才输出一个 9 字节帧
+#[derive(Default)]
+pub struct MotorFrameCodec;
+
+impl Decoder for MotorFrameCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        loop {
+            let Some(start) = src.windows(2).position(|w| w == FRAME_HEAD) else {
+                // 末尾可能是下一帧帧头的第一个字节，保留下来等后续数据
+                let keep = usize::from(src.last() == Some(&FRAME_HEAD[0]));
+                if src.len() > keep {
+                    warn!("Discarding motor bytes {:X?}", &src[..src.len() - keep]);
+                    src.advance(src.len() - keep);
+                }
+                return Ok(None);
+            };
+            if start > 0 {
+                warn!("Discarding motor bytes {:X?}", &src[..start]);
+                src.advance(start);
+            }
+            if src.len() < FRAME_LEN {
+                src.reserve(FRAME_LEN - src.len());
+                return Ok(None);
+            }
+            if src[7..FRAME_LEN] != FRAME_TAIL {
+                // 帧尾不对时跳过这个帧头，从后面的数据重新找
+                warn!("Bad motor frame tail {:X?}", &src[..FRAME_LEN]);
+                src.advance(FRAME_HEAD.len());
+                continue;
+            }
+            return Ok(Some(src.split_to(FRAME_LEN)));
+        }
+    }
+}
+
+impl Encoder<Bytes> for MotorFrameCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> io::Result<()> {
+        dst.extend_from_slice(&item);
+        Ok(())
+    }
+}
+
+impl MotorLink {
+    /// transport 一般为使用 MotorFrameCodec 的串口 Framed，每次收到一个完整的帧
+    pub fn spawn<T>(
+        transport: T,
+        events: broadcast::Sender<MotorEvent>,
+        capture: Arc<Capture>,
+        estop_command: u8,
+    ) -> Self
+    where
+        T: Sink<Bytes, Error = io::Error> + Stream<Item = io::Result<BytesMut>> + Send + Unpin + 'static,
+    {
+        let (cmd_tx, cmd_rx) = mpsc::channel(QUEUE_SIZE);
+        let (estop_tx, estop_rx) = mpsc::channel(1);
+        tokio::spawn(run(transport, cmd_rx, estop_rx, events, capture, estop_command));
+        MotorLink { cmd_tx, estop_tx }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.cmd_tx.is_closed()
+    }
+
+    /// 排队发送命令并等待同一命令号的应答
+    pub async fn request(&self, command: u8, value: u32, timeout: Duration) -> AppResult<BytesMut> {
+        let (reply, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(MotorRequest {
+                command,
+                value,
+                timeout,
+                reply,
+            })
+            .await
+            .map_err(|_| AppError::Disconnected(Device::Motor))?;
+        rx.await.map_err(|_| AppError::Disconnected(Device::Motor))?
+    }
+
+    /// 急停插队发送，不等待当前命令的应答
+    pub async fn estop(&self) -> AppResult<()> {
+        let (reply, rx) = oneshot::channel();
+        self.estop_tx
+            .send(reply)
+            .await
+            .map_err(|_| AppError::Disconnected(Device::Motor))?;
+        rx.await.map_err(|_| AppError::Disconnected(Device::Motor))?
+    }
+}
+
+/// 对不上正在等待的命令号的帧只广播检测上报和联锁上报，其余帧（如超时后迟到的应答）记录后丢弃
+fn dispatch_unsolicited(bytes: &[u8], events: &broadcast::Sender<MotorEvent>) {
+    let value = match parse_frame(bytes) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Unexpected motor frame {:X?}: {}", bytes, e);
+            return;
+        }
+    };
+    // 没有订阅者时直接丢弃
+    match bytes[2] {
+        CMD_INTERLOCK => {
+            let _ = events.send(MotorEvent::Interlock(value));
+        }
+        REPORT_COMMAND => {
+            let _ = events.send(MotorEvent::Angle(f32::from_bits(value)));
+            if bytes[3] == 0x09 {
+                let _ = events.send(MotorEvent::Finished);
+            }
+        }
+        command => warn!("Dropping motor frame for command {}: {:X?}", command, bytes),
+    }
+}
+
+async fn run<T>(
+    mut framed: T,
+    mut cmd_rx: mpsc::Receiver<MotorRequest>,
+    mut estop_rx: mpsc::Receiver<oneshot::Sender<AppResult<()>>>,
+    events: broadcast::Sender<MotorEvent>,
+    capture: Arc<Capture>,
+    estop_command: u8,
+) where
+    T: Sink<Bytes, Error = io::Error> + Stream<Item = io::Result<BytesMut>> + Unpin,
+{
+    let mut in_flight: Option<InFlight> = None;
+    let error = loop {
+        let deadline = in_flight.as_ref().map(|f| f.deadline);
+        tokio::select! {
+            biased;
+            reply = estop_rx.recv() => {
+                let Some(reply) = reply else { break None };
+                let frame = encode_frame(estop_command, 0);
+                capture.record(Device::Motor, Direction::Tx, &frame);
+                let res = framed
+                    .send(frame)
+                    .await
+                    .map_err(|e| AppError::io(Device::Motor, e));
+                let _ = reply.send(res);
+            }
+            frame = framed.next() => {
+                let bytes = match frame {
+                    Some(Ok(bytes)) => bytes,
+                    Some(Err(e)) => break Some(AppError::io(Device::Motor, e)),
+                    None => break Some(AppError::Disconnected(Device::Motor)),
+                };
+                // 按解码后的完整帧记录，抓包文件中每条接收记录都能按帧解析
+                capture.record(Device::Motor, Direction::Rx, &bytes);
+                match in_flight.take() {
+                    Some(f) if bytes.len() == FRAME_LEN && bytes[2] == f.command => {
+                        let _ = f.reply.send(Ok(bytes));
+                    }
+                    other => {
+                        in_flight = other;
+                        dispatch_unsolicited(&bytes, &events);
+                    }
+                }
+            }
+            _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                if let Some(f) = in_flight.take() {
+                    let _ = f.reply.send(Err(AppError::Timeout(Device::Motor)));
+                }
+            }
+            req = cmd_rx.recv(), if in_flight.is_none() => {
+                let Some(req) = req else { break None };
+                let frame = encode_frame(req.command, req.value);
+                capture.record(Device::Motor, Direction::Tx, &frame);
+                match framed.send(frame).await {
+                    Ok(_) => {
+                        in_flight = Some(InFlight {
+                            command: req.command,
+                            deadline: Instant::now() + req.timeout,
+                            reply: req.reply,
+                        });
+                    }
+                    Err(e) => {
+                        let _ = req.reply.send(Err(AppError::io(Device::Motor, e)));
+                    }
+                }
+            }
+        }
+    };
+    if let Some(e) = error {
+        error!("Motor link closed: {}", e);
+        if let Some(f) = in_flight.take() {
+            let _ = f.reply.send(Err(e.clone()));
+        }
+        let _ = events.send(MotorEvent::Closed(e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc as chan;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// 用通道模拟串口，测试端读取发出的帧并注入控制器的应答
+    struct FakePort {
+        tx: chan::UnboundedSender<Bytes>,
+        rx: chan::UnboundedReceiver<io::Result<BytesMut>>,
+    }
+
+    impl Stream for FakePort {
+        type Item = io::Result<BytesMut>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            self.rx.poll_next_unpin(cx)
+        }
+    }
+
+    impl Sink<Bytes> for FakePort {
+        type Error = io::Error;
+
+        fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.tx.poll_ready(cx).map_err(|_| io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, item: Bytes) -> io::Result<()> {
+            self.tx.start_send(item).map_err(|_| io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    struct Controller {
+        sent: chan::UnboundedReceiver<Bytes>,
+        reply: chan::UnboundedSender<io::Result<BytesMut>>,
+    }
+
+    impl Controller {
+        fn send(&self, command: u8, value: u32) {
+            let frame = BytesMut::from(&encode_frame(command, value)[..]);
+            let _ = self.reply.unbounded_send(Ok(frame));
+        }
+
+        async fn next_command(&mut self) -> u8 {
+            self.sent.next().await.expect("link closed")[2]
+        }
+    }
+
+    fn link() -> (MotorLink, Controller, broadcast::Receiver<MotorEvent>) {
+        let (tx, sent) = chan::unbounded();
+        let (reply, rx) = chan::unbounded();
+        let (events, events_rx) = broadcast::channel(EVENT_CHANNEL_SIZE);
+        let link = MotorLink::spawn(FakePort { tx, rx }, events, Arc::new(Capture::default()), 8);
+        (link, Controller { sent, reply }, events_rx)
+    }
+
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    #[tokio::test]
+    async fn reply_matches_command() {
+        let (link, mut ctrl, _events) = link();
+        let req = tokio::spawn(async move { link.request(4, 0, TIMEOUT).await });
+        assert_eq!(ctrl.next_command().await, 4);
+        ctrl.send(4, 90f32.to_bits());
+        let bytes = req.await.unwrap().unwrap();
+        assert_eq!(parse_frame(&bytes).unwrap(), 90f32.to_bits());
+    }
+
+    #[tokio::test]
+    async fn report_frames_are_broadcast() {
+        let (_link, ctrl, mut events) = link();
+        ctrl.send(REPORT_COMMAND, 12.5f32.to_bits());
+        // 最低字节为 0x09 的上报帧表示检测结束
+        ctrl.send(REPORT_COMMAND, 0x09);
+        ctrl.send(CMD_INTERLOCK, 0b10);
+        assert!(matches!(events.recv().await.unwrap(), MotorEvent::Angle(a) if a == 12.5));
+        assert!(matches!(events.recv().await.unwrap(), MotorEvent::Angle(_)));
+        assert!(matches!(events.recv().await.unwrap(), MotorEvent::Finished));
+        assert!(matches!(events.recv().await.unwrap(), MotorEvent::Interlock(0b10)));
+    }
+
+    #[tokio::test]
+    async fn other_frames_are_dropped() {
+        let (_link, ctrl, mut events) = link();
+        ctrl.send(2, 1000);
+        ctrl.send(REPORT_COMMAND, 30f32.to_bits());
+        assert!(matches!(events.recv().await.unwrap(), MotorEvent::Angle(a) if a == 30.0));
+    }
+
+    #[tokio::test]
+    async fn late_reply_after_timeout_is_dropped() {
+        let (link, mut ctrl, mut events) = link();
+        let res = link.request(4, 0, Duration::from_millis(20)).await;
+        assert!(matches!(res, Err(AppError::Timeout(Device::Motor))));
+        assert_eq!(ctrl.next_command().await, 4);
+        ctrl.send(4, 45f32.to_bits());
+        // 迟到的应答不能当作下一条命令的应答，也不能当作角度上报
+        let req = {
+            let link = link.clone();
+            tokio::spawn(async move { link.request(2, 500, TIMEOUT).await })
+        };
+        assert_eq!(ctrl.next_command().await, 2);
+        ctrl.send(2, 500);
+        let bytes = req.await.unwrap().unwrap();
+        assert_eq!(bytes[2], 2);
+        assert!(events.try_recv().is_err());
+    }
+
+    fn decode_all(codec: &mut MotorFrameCodec, buf: &mut BytesMut) -> Vec<BytesMut> {
+        std::iter::from_fn(|| codec.decode(buf).unwrap()).collect()
+    }
+
+    #[test]
+    fn decoder_joins_split_chunks() {
+        let frame = encode_frame(4, 90f32.to_bits());
+        let mut codec = MotorFrameCodec;
+        let mut buf = BytesMut::from(&frame[..4]);
+        assert!(decode_all(&mut codec, &mut buf).is_empty());
+        buf.extend_from_slice(&frame[4..]);
+        assert_eq!(decode_all(&mut codec, &mut buf), vec![BytesMut::from(&frame[..])]);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn decoder_splits_merged_chunks() {
+        let first = encode_frame(REPORT_COMMAND, 10f32.to_bits());
+        let second = encode_frame(REPORT_COMMAND, 20f32.to_bits());
+        let mut buf = BytesMut::from(&first[..]);
+        buf.extend_from_slice(&second);
+        let frames = decode_all(&mut MotorFrameCodec, &mut buf);
+        assert_eq!(frames, vec![BytesMut::from(&first[..]), BytesMut::from(&second[..])]);
+    }
+
+    #[test]
+    fn decoder_resyncs_on_garbage() {
+        let frame = encode_frame(2, 500);
+        // 帧前的杂散字节、帧尾错误的半帧都要丢弃
+        let mut buf = BytesMut::from(&[0x00, 0xEF, 0x12, 0xEF, 0xFE, 2, 0, 0, 0, 0, 0x00, 0x00][..]);
+        buf.extend_from_slice(&frame);
+        buf.extend_from_slice(&[0x33, 0xEF]);
+        let frames = decode_all(&mut MotorFrameCodec, &mut buf);
+        assert_eq!(frames, vec![BytesMut::from(&frame[..])]);
+        // 末尾可能是下一帧帧头的字节要保留
+        assert_eq!(&buf[..], &[0xEF]);
+    }
+
+    #[tokio::test]
+    async fn link_handles_split_and_merged_reads() {
+        let (port, mut ctrl) = tokio::io::duplex(64);
+        let (events, mut events_rx) = broadcast::channel(EVENT_CHANNEL_SIZE);
+        let framed = tokio_util::codec::Framed::new(port, MotorFrameCodec);
+        let link = MotorLink::spawn(framed, events, Arc::new(Capture::default()), 8);
+        let req = tokio::spawn(async move { link.request(4, 0, TIMEOUT).await });
+        let mut sent = [0u8; FRAME_LEN];
+        ctrl.read_exact(&mut sent).await.unwrap();
+        assert_eq!(sent[2], 4);
+        // 应答分两次到达，后半块还连着一帧角度上报
+        let reply = encode_frame(4, 90f32.to_bits());
+        let report = encode_frame(REPORT_COMMAND, 12.5f32.to_bits());
+        ctrl.write_all(&reply[..4]).await.unwrap();
+        ctrl.flush().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let mut rest = reply[4..].to_vec();
+        rest.extend_from_slice(&report);
+        ctrl.write_all(&rest).await.unwrap();
+        let bytes = req.await.unwrap().unwrap();
+        assert_eq!(parse_frame(&bytes).unwrap(), 90f32.to_bits());
+        assert!(matches!(events_rx.recv().await.unwrap(), MotorEvent::Angle(a) if a == 12.5));
+    }
+
+    #[tokio::test]
+    async fn estop_jumps_ahead_of_pending_request() {
+        let (link, mut ctrl, _events) = link();
+        let req = {
+            let link = link.clone();
+            tokio::spawn(async move { link.request(6, 0, Duration::from_secs(5)).await })
+        };
+        assert_eq!(ctrl.next_command().await, 6);
+        // 手动正转还在等待应答时急停应立即发出
+        link.estop().await.unwrap();
+        assert_eq!(ctrl.next_command().await, 8);
+        ctrl.send(6, 0);
+        assert!(req.await.unwrap().is_ok());
+    }
+}
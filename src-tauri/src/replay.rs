@@ -0,0 +1,388 @@
+use crate::acquisition::{write_records, AngleTrigger, Joiner, ScanConfig, ScanSink, Sensor, SensorSample};
+use crate::device;
+use crate::error::{AppError, AppResult, Device};
+use crate::serial::{hall_statistics, LaserData};
+use crate::sqlite::{
+    create_project, get_hall_rows, get_project, set_project_status, PROJECT_ABORTED, PROJECT_FINISHED, PROJECT_REPLAY,
+};
+use crate::AppWrapper;
+use chrono::Local;
+use log::{error, info, warn};
+use serde::Deserialize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// 没有采样时间时相邻两个角度的回放间隔
+const DEFAULT_INTERVAL_MS: i64 = 50;
+/// 激光点按极角分组时允许的误差，单位度
+const ANGLE_TOLERANCE: f32 = 0.01;
+
+/// 回放数据来源
+#[derive(Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ReplaySource {
+    /// 数据库中已保存的项目，只有霍尔数据
+    Database { parent_id: i64 },
+    /// 采集时导出的霍尔原始数据文件和激光点云文件
+    Files {
+        hall_path: Option<String>,
+        laser_path: Option<String>,
+    },
+}
+
+/// 一个角度上回放的数据
+struct ReplayFrame {
+    angle: f32,
+    /// 毫秒时间戳，用于还原采样间隔
+    time: i64,
+    hall: Option<Vec<i32>>,
+    laser: Option<Vec<LaserData>>,
+}
+
+async fn read_file(path: &str) -> AppResult<String> {
+    tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| AppError::io(Device::File, format!("无法读取{}: {}", path, e)))
+}
+
+fn parse_hall_line(fields: &[&str]) -> Option<(f32, Vec<i32>)> {
+    let angle = fields.first()?.parse::<f32>().ok()?;
+    let data = fields[1..].iter().map(|f| f.parse::<i32>().ok()).collect::<Option<Vec<i32>>>()?;
+    (data.len() == 9).then_some((angle, data))
+}
+
+/// 解析霍尔原始数据文件，每行依次为角度和 9 个通道读数
+fn parse_hall_file(text: &str) -> AppResult<Vec<(f32, Vec<i32>)>> {
+    let mut rows = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+        match parse_hall_line(&fields) {
+            Some(row) => rows.push(row),
+            None => return Err(AppError::parse(Device::File, format!("霍尔数据第{}行格式错误", i + 1))),
+        }
+    }
+    Ok(rows)
+}
+
+/// 点的极角，折算到 [0, 180)，距离为负的点与同一轮廓的其他点相差 180°
+fn point_key(p: &LaserData) -> f32 {
+    p.y.atan2(p.x).to_degrees().rem_euclid(180.0)
+}
+
+fn same_key(a: f32, b: f32) -> bool {
+    let d = (a - b).abs();
+    d.min(180.0 - d) < ANGLE_TOLERANCE
+}
+
+/// 解析激光点云文件，文件中没有角度，按相邻点的极角把点分成各个角度的轮廓
+fn parse_laser_file(text: &str) -> AppResult<Vec<(f32, Vec<LaserData>)>> {
+    let mut profiles: Vec<(f32, Vec<LaserData>)> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let fields = match line.split_whitespace().map(|f| f.parse::<f32>()).collect::<Result<Vec<f32>, _>>() {
+            Ok(f) => f,
+            Err(_) => return Err(AppError::parse(Device::File, format!("激光数据第{}行格式错误", i + 1))),
+        };
+        let point = match fields[..] {
+            [] => continue,
+            [x, y, z] => LaserData { x, y, z },
+            _ => return Err(AppError::parse(Device::File, format!("激光数据第{}行格式错误", i + 1))),
+        };
+        let key = point_key(&point);
+        match profiles.last_mut() {
+            Some((k, points)) if same_key(*k, key) => points.push(point),
+            _ => profiles.push((key, vec![point])),
+        }
+    }
+    Ok(profiles)
+}
+
+/// 按顺序把激光轮廓对到角度相同的霍尔数据上，没有霍尔数据时按轮廓本身的角度回放
+fn merge_files(hall: Vec<(f32, Vec<i32>)>, laser: Vec<(f32, Vec<LaserData>)>) -> Vec<ReplayFrame> {
+    let mut frames: Vec<ReplayFrame> = hall
+        .into_iter()
+        .enumerate()
+        .map(|(i, (angle, data))| ReplayFrame {
+            angle,
+            time: i as i64 * DEFAULT_INTERVAL_MS,
+            hall: Some(data),
+            laser: None,
+        })
+        .collect();
+    if frames.is_empty() {
+        return laser
+            .into_iter()
+            .enumerate()
+            .map(|(i, (_, points))| ReplayFrame {
+                angle: point_key(&points[0]),
+                time: i as i64 * DEFAULT_INTERVAL_MS,
+                hall: None,
+                laser: Some(points),
+            })
+            .collect();
+    }
+    let mut cursor = 0;
+    let mut unmatched = 0;
+    for (key, points) in laser {
+        match frames[cursor..].iter().position(|f| same_key(f.angle.rem_euclid(180.0), key)) {
+            Some(i) => {
+                cursor += i;
+                frames[cursor].laser = Some(points);
+                cursor += 1;
+            }
+            None => unmatched += 1,
+        }
+        if cursor >= frames.len() {
+            break;
+        }
+    }
+    if unmatched > 0 {
+        warn!("Replay: {} laser profiles without matching hall angle", unmatched);
+    }
+    frames
+}
+
+/// 读取回放数据，返回各角度数据以及霍尔、激光的安装距离
+async fn load(app: &AppWrapper, source: &ReplaySource) -> AppResult<(String, Vec<ReplayFrame>, f32, f32)> {
+    match source {
+        ReplaySource::Database { parent_id } => {
+            let project = get_project(*parent_id)?;
+            let mut last = 0;
+            let frames = get_hall_rows(*parent_id)?
+                .into_iter()
+                .map(|(angle, time, data)| {
+                    // 旧版本数据库没有采样时间
+                    last = time.unwrap_or(last + DEFAULT_INTERVAL_MS);
+                    ReplayFrame {
+                        angle,
+                        time: last,
+                        hall: Some(data),
+                        laser: None,
+                    }
+                })
+                .collect();
+            Ok((project.name, frames, project.hall_d, project.laser_d))
+        }
+        ReplaySource::Files { hall_path, laser_path } => {
+            if hall_path.is_none() && laser_path.is_none() {
+                return Err(AppError::InvalidInput("未指定回放文件".into()));
+            }
+            let hall = match hall_path {
+                Some(p) => parse_hall_file(&read_file(p).await?)?,
+                None => Vec::new(),
+            };
+            let laser = match laser_path {
+                Some(p) => parse_laser_file(&read_file(p).await?)?,
+                None => Vec::new(),
+            };
+            let (hall_d, laser_d) = {
+                let settings = app.settings.lock().await;
+                (settings.hall_d, settings.laser_d)
+            };
+            let name = hall_path.clone().or(laser_path.clone()).unwrap_or_default();
+            Ok((name, merge_files(hall, laser), hall_d, laser_d))
+        }
+    }
+}
+
+/// 回放已保存的数据，经过与实时采集相同的写入和推送流程，返回新建的项目 id
+///
+/// speed 为回放倍速，1 为按原始间隔回放，0 为不等待尽快回放。回放不写输出文件，可用 stop_work 停止。
+pub async fn start_replay(app: Arc<AppWrapper>, source: ReplaySource, speed: f32) -> AppResult<i64> {
+    if !speed.is_finite() || speed < 0.0 {
+        return Err(AppError::InvalidInput("回放倍速不合法".into()));
+    }
+    let (name, frames, hall_d, laser_d) = load(&app, &source).await?;
+    if frames.is_empty() {
+        return Err(AppError::InvalidInput("没有可回放的数据".into()));
+    }
+    // 与开始采集相同，占用设备后健康监测不再发送探测命令
+    if !device::claim(&app, &app.scanning).await {
+        return Err(AppError::State("采集进行中，无法回放".into()));
+    }
+    if app.moving.load(Ordering::SeqCst) {
+        app.scanning.store(false, Ordering::SeqCst);
+        return Err(AppError::State("电机回零或定位中，无法回放".into()));
+    }
+    let parent_id = match create_project(format!("回放-{}", name), PROJECT_REPLAY, hall_d, laser_d) {
+        Ok(id) => id,
+        Err(e) => {
+            app.scanning.store(false, Ordering::SeqCst);
+            return Err(e);
+        }
+    };
+    let config = ScanConfig {
+        parent_id,
+        samples: 1,
+        max_std: None,
+        laser_d,
+        hall_paths: None,
+        laser_path: None,
+        capture: None,
+    };
+    let sink = match ScanSink::open(app.clone(), &config).await {
+        Ok(sink) => sink,
+        Err(e) => {
+            let _ = set_project_status(parent_id, PROJECT_ABORTED);
+            app.scanning.store(false, Ordering::SeqCst);
+            return Err(e);
+        }
+    };
+    let _ = app.stop_tx.send(false);
+    let stop_rx = app.stop_tx.subscribe();
+    info!("Replay started, {} frames, speed {}", frames.len(), speed);
+    tokio::spawn(run_replay(app, parent_id, frames, speed, sink, stop_rx));
+    Ok(parent_id)
+}
+
+/// 回放数据中缺少的传感器数据
+fn missing(seq: u64, sensor: Sensor) -> SensorSample {
+    SensorSample::Missing {
+        seq,
+        sensor,
+        error: AppError::parse(Device::File, "回放数据中该角度没有此传感器的数据"),
+    }
+}
+
+async fn run_replay(
+    app: Arc<AppWrapper>,
+    parent_id: i64,
+    frames: Vec<ReplayFrame>,
+    speed: f32,
+    mut sink: ScanSink,
+    mut stop_rx: watch::Receiver<bool>,
+) {
+    let start = Instant::now();
+    let t0 = frames[0].time;
+    let use_hall = frames.iter().any(|f| f.hall.is_some());
+    let use_laser = frames.iter().any(|f| f.laser.is_some());
+    // 与实时采集一样经同步器按序号合并后写入
+    let mut joiner = Joiner::new(use_hall, use_laser);
+    let mut seq = 0;
+    let mut result: AppResult<()> = Ok(());
+    for frame in frames {
+        let stopped = if speed > 0.0 {
+            let offset = (frame.time - t0).max(0) as f64 / 1000.0 / speed as f64;
+            tokio::select! {
+                _ = tokio::time::sleep_until(start + Duration::from_secs_f64(offset)) => false,
+                res = stop_rx.wait_for(|s| *s) => res.is_ok(),
+            }
+        } else {
+            *stop_rx.borrow()
+        };
+        if stopped {
+            info!("Replay stopping...");
+            break;
+        }
+        seq += 1;
+        sink.on_angle(frame.angle);
+        joiner.insert(&AngleTrigger {
+            seq,
+            angle: frame.angle,
+            time: Local::now(),
+        });
+        if use_hall {
+            joiner.arrive(match frame.hall.and_then(|data| hall_statistics(&[data])) {
+                Some(stat) => SensorSample::Hall { seq, stat },
+                None => missing(seq, Sensor::Hall),
+            });
+        }
+        if use_laser {
+            joiner.arrive(match frame.laser {
+                Some(points) => SensorSample::Laser { seq, points },
+                None => missing(seq, Sensor::Laser),
+            });
+        }
+        result = write_records(&mut sink, joiner.take_ready(seq)).await;
+        if result.is_err() {
+            break;
+        }
+    }
+    if result.is_ok() {
+        result = write_records(&mut sink, joiner.drain()).await;
+    }
+    if let Err(e) = &result {
+        error!("Replay aborted: {}", e);
+        app.notify("error", "数据保存失败", format!("回放已中止: {}", e));
+    }
+    let status = if result.is_ok() { PROJECT_FINISHED } else { PROJECT_ABORTED };
+    if let Err(e) = set_project_status(parent_id, status) {
+        error!("Error updating project status: {}", e);
+    }
+    if result.is_ok() {
+        sink.judge().await;
+    }
+    sink.finish();
+    app.scanning.store(false, Ordering::SeqCst);
+    info!("Replay stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, y: f32, z: f32) -> LaserData {
+        LaserData { x, y, z }
+    }
+
+    #[test]
+    fn hall_file_skips_blank_lines() {
+        let text = " 0 1 2 3 4 5 6 7 8 9\n\n 1.5 -1 -2 -3 -4 -5 -6 -7 -8 -9\n";
+        let rows = parse_hall_file(text).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].0, 1.5);
+        assert_eq!(rows[1].1, vec![-1, -2, -3, -4, -5, -6, -7, -8, -9]);
+    }
+
+    #[test]
+    fn hall_file_reports_bad_line() {
+        let text = " 0 1 2 3 4 5 6 7 8 9\n 1 1 2 3\n";
+        let err = parse_hall_file(text).err().unwrap();
+        assert!(err.to_string().contains("第2行"));
+        assert!(parse_hall_file(" 0 1 2 3 4 5 6 7 8 x\n").is_err());
+    }
+
+    #[test]
+    fn laser_file_groups_points_by_polar_angle() {
+        // 距离为负的点与同一轮廓相差 180°
+        let text = "1 0 0\n2 0 1\n-1 0 2\n0 1 0\n0 -2 1\n";
+        let profiles = parse_laser_file(text).unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].1.len(), 3);
+        assert!(same_key(profiles[0].0, 0.0));
+        assert_eq!(profiles[1].1.len(), 2);
+        assert!(same_key(profiles[1].0, 90.0));
+        assert!(parse_laser_file("1 0\n").is_err());
+    }
+
+    #[test]
+    fn merge_attaches_profiles_to_matching_angles() {
+        let hall = vec![(0.0, vec![0; 9]), (90.0, vec![1; 9]), (180.0, vec![2; 9])];
+        let laser = vec![
+            (45.0, vec![point(1.0, 1.0, 0.0)]),
+            (90.0, vec![point(0.0, 1.0, 0.0)]),
+            (0.0, vec![point(-1.0, 0.0, 0.0)]),
+        ];
+        let frames = merge_files(hall, laser);
+        assert_eq!(frames.len(), 3);
+        // 45° 没有对应的霍尔角度，180° 与 0° 的轮廓在同一条直线上
+        assert!(frames[0].laser.is_none());
+        assert!(frames[1].laser.is_some());
+        assert!(frames[2].laser.is_some());
+        assert_eq!(frames[2].time, 2 * DEFAULT_INTERVAL_MS);
+    }
+
+    #[test]
+    fn merge_without_hall_uses_profile_angles() {
+        let laser = vec![(0.0, vec![point(1.0, 0.0, 0.0)]), (90.0, vec![point(0.0, 2.0, 0.0)])];
+        let frames = merge_files(Vec::new(), laser);
+        assert_eq!(frames.len(), 2);
+        assert!(frames.iter().all(|f| f.hall.is_none() && f.laser.is_some()));
+        assert!(same_key(frames[1].angle, 90.0));
+    }
+}
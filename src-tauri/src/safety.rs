@@ -0,0 +1,201 @@
+use crate::error::{AppError, AppResult};
+use crate::motor::MotorEvent;
+use crate::AppWrapper;
+use log::warn;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::sync::broadcast::error::RecvError;
+
+/// 急停使用现有的停止转动命令
+pub const CMD_ESTOP: u8 = 8;
+/// 读取联锁状态，控制器也会用同一命令号主动上报状态变化，需要扩展固件
+pub const CMD_INTERLOCK: u8 = 19;
+/// 清除控制器中的故障锁定，需要扩展固件
+pub const CMD_RESET_FAULT: u8 = 20;
+
+const BIT_DOOR_OPEN: u32 = 1 << 0;
+const BIT_OVERCURRENT: u32 = 1 << 1;
+const BIT_LIMIT_HIT: u32 = 1 << 2;
+const BIT_ESTOP: u32 = 1 << 3;
+
+/// 电机联锁状态，latched 为真时拒绝一切运动命令，直到人工确认
+#[derive(Clone, Default, PartialEq, Serialize)]
+pub struct InterlockState {
+    pub door_open: bool,
+    pub overcurrent: bool,
+    pub limit_hit: bool,
+    pub estop: bool,
+    pub latched: bool,
+    /// 触发锁定的原因
+    pub reason: Option<String>,
+}
+
+impl InterlockState {
+    fn set_bits(&mut self, bits: u32) {
+        self.door_open = bits & BIT_DOOR_OPEN != 0;
+        self.overcurrent = bits & BIT_OVERCURRENT != 0;
+        self.limit_hit = bits & BIT_LIMIT_HIT != 0;
+        self.estop = bits & BIT_ESTOP != 0;
+    }
+
+    fn active(&self) -> bool {
+        self.door_open || self.overcurrent || self.limit_hit || self.estop
+    }
+
+    fn describe(&self) -> String {
+        let mut items = Vec::new();
+        if self.door_open {
+            items.push("防护门打开");
+        }
+        if self.overcurrent {
+            items.push("电机过流");
+        }
+        if self.limit_hit {
+            items.push("触发限位");
+        }
+        if self.estop {
+            items.push("急停按下");
+        }
+        items.join("、")
+    }
+}
+
+fn emit_interlock(app: &AppWrapper, state: &InterlockState) {
+    if let Err(e) = app.app_handler.emit("interlock", state.clone()) {
+        warn!("Failed to emit interlock: {}", e);
+    }
+}
+
+/// 锁定电机并中止采集
+async fn latch(app: &AppWrapper, reason: String) {
+    let state = {
+        let mut state = app.interlock.lock().await;
+        if !state.latched {
+            state.latched = true;
+            state.reason = Some(reason.clone());
+        }
+        state.clone()
+    };
+    let _ = app.stop_tx.send(true);
+    app.notify("error", "电机故障锁定", reason);
+    emit_interlock(app, &state);
+}
+
+/// 处理控制器返回或主动上报的联锁状态，出现故障时锁定
+pub async fn report_interlock(app: &AppWrapper, bits: u32) {
+    let (state, changed) = {
+        let mut state = app.interlock.lock().await;
+        let before = state.clone();
+        state.set_bits(bits);
+        (state.clone(), *state != before)
+    };
+    if state.active() && !state.latched {
+        latch(app, state.describe()).await;
+    } else if changed {
+        emit_interlock(app, &state);
+    }
+}
+
+pub async fn ensure_motion_allowed(app: &AppWrapper) -> AppResult<()> {
+    let state = app.interlock.lock().await;
+    if state.latched {
+        return Err(AppError::State(format!(
+            "电机处于故障锁定状态({})，请排除故障并确认后再操作",
+            state.reason.clone().unwrap_or_default()
+        )));
+    }
+    Ok(())
+}
+
+/// 控制器是否支持联锁命令，见 docs/motor_protocol.md
+async fn interlock_supported(app: &AppWrapper) -> bool {
+    app.settings.lock().await.motor_extended_protocol
+}
+
+/// 从控制器读取联锁状态，固件不支持联锁命令时返回本地状态
+pub async fn read_interlock(app: &AppWrapper) -> AppResult<InterlockState> {
+    if !interlock_supported(app).await {
+        return Ok(app.interlock.lock().await.clone());
+    }
+    let timeout = app.settings.lock().await.motor_serial.timeout();
+    let bits = app.talk_with_motor(CMD_INTERLOCK, 0, timeout).await?;
+    report_interlock(app, bits).await;
+    Ok(app.interlock.lock().await.clone())
+}
+
+/// 处理控制器主动上报的联锁状态，未开启扩展协议时忽略
+pub fn spawn_interlock_monitor(app: Arc<AppWrapper>) {
+    let mut events = app.motor_events.subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(MotorEvent::Interlock(bits)) => {
+                    if interlock_supported(&app).await {
+                        report_interlock(&app, bits).await;
+                    }
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// 急停在电机链路任务中插队发送，不等待正在进行的应答，发送失败也会锁定电机
+#[tauri::command]
+pub async fn emergency_stop(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<String> {
+    let sent = match app.motor_link().await {
+        Ok(link) => link.estop().await,
+        Err(e) => Err(e),
+    };
+    {
+        let mut state = app.interlock.lock().await;
+        state.estop = true;
+    }
+    latch(&app, "软件急停".into()).await;
+    sent?;
+    Ok("已急停".into())
+}
+
+#[tauri::command]
+pub async fn get_interlock(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<InterlockState> {
+    if app.motor_link().await.is_err() {
+        return Ok(app.interlock.lock().await.clone());
+    }
+    read_interlock(&app).await
+}
+
+/// 确认故障，控制器仍报告故障时拒绝解除锁定；固件不支持联锁命令时只解除本地锁定
+#[tauri::command]
+pub async fn acknowledge_fault(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<String> {
+    if !interlock_supported(&app).await {
+        let state = {
+            let mut state = app.interlock.lock().await;
+            *state = InterlockState::default();
+            state.clone()
+        };
+        emit_interlock(&app, &state);
+        return Ok("故障已解除".into());
+    }
+    let timeout = app.settings.lock().await.motor_serial.timeout();
+    app.talk_with_motor(CMD_RESET_FAULT, 0, timeout).await?;
+    // 给控制器留出复位时间再读取状态
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let bits = app.talk_with_motor(CMD_INTERLOCK, 0, timeout).await?;
+    let state = {
+        let mut state = app.interlock.lock().await;
+        state.set_bits(bits);
+        if !state.active() {
+            state.latched = false;
+            state.reason = None;
+        }
+        state.clone()
+    };
+    emit_interlock(&app, &state);
+    if state.latched {
+        return Err(AppError::State(format!("故障仍存在: {}", state.describe())));
+    }
+    Ok("故障已解除".into())
+}
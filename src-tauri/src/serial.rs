@@ -1,10 +1,19 @@
-use crate::sqlite::{create_project, insert_data};
-use crate::{AppWrapper, MessagePayload, Payload, PortInfo, SerialPortList};
+use crate::acquisition::{spawn_scan, ScanConfig, ScanSink, MAX_HALL_SAMPLES};
+use crate::buffer::{RingSlice, Sequenced};
+use crate::capture::{self, capture_dir, capture_file_name, CaptureRecord};
+use crate::device::{self, device_state, is_open, DeviceStatusList, LaserScanner, ProbeResult};
+use crate::error::{AppError, AppResult, Device};
+use crate::logging::{self, LogLine};
+use crate::motion::{self, MoveResult};
+use crate::replay::{self, ReplaySource};
+use crate::settings::{AppSettings, HomingSettings, HomingSource, LaserSettings, MotionProfile, SerialSettings, UsbId};
+use crate::sqlite::{create_project, set_project_status, PROJECT_ABORTED, PROJECT_SCAN};
+use crate::{AppWrapper, LaserProfile, Payload, PortInfo, SerialPortList};
+use log::{info, trace, warn};
 use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tauri::Emitter;
-use tokio::fs::OpenOptions;
-use tokio::io::AsyncWriteExt;
 use tokio_serial::SerialPortType;
 #[derive(Clone, serde::Serialize)]
 pub struct LaserData {
@@ -15,7 +24,7 @@ pub struct LaserData {
 
 pub fn hall_parse_data(received: &[u8]) -> Option<Vec<i32>> {
     if received.len() < 44 {
-        println!("{:?}", received);
+        trace!("{:?}", received);
         return None;
     }
 
@@ -34,6 +43,77 @@ pub fn hall_parse_data(received: &[u8]) -> Option<Vec<i32>> {
     Some(result)
 }
 
+/// 霍尔 ADC 为 24 位，读数超出此范围说明不是霍尔板的应答
+const HALL_ADC_RANGE: std::ops::RangeInclusive<i32> = -8388608..=8388607;
+
+/// 是否为完整的霍尔应答帧：长度正好 44 字节，9 个通道读数都在 ADC 量程内
+pub fn hall_frame_valid(received: &[u8]) -> bool {
+    if received.len() != 44 {
+        return false;
+    }
+    hall_parse_data(received).is_some_and(|data| data.iter().all(|v| HALL_ADC_RANGE.contains(v)))
+}
+
+/// 霍尔原始读数换算为电压
+pub fn hall_to_volts(data: &[i32]) -> Vec<f32> {
+    data.iter()
+        .map(|d| (1650_f32 * (*d as f32) / 8388607_f32) / 64_f32)
+        .collect()
+}
+
+/// 同一角度下多次霍尔采样的统计结果，各字段按通道排列
+#[derive(Clone, serde::Serialize)]
+pub struct HallStat {
+    pub samples: u32,
+    pub mean: Vec<f32>,
+    pub std: Vec<f32>,
+    pub min: Vec<i32>,
+    pub max: Vec<i32>,
+}
+
+impl HallStat {
+    /// 均值四舍五入后的通道数据，用于写入原有的整数数据表
+    pub fn rounded_mean(&self) -> Vec<i32> {
+        self.mean.iter().map(|m| m.round() as i32).collect()
+    }
+
+    /// 所有通道中最大的标准差
+    pub fn max_std(&self) -> f32 {
+        self.std.iter().cloned().fold(0_f32, f32::max)
+    }
+}
+
+pub fn hall_statistics(readings: &[Vec<i32>]) -> Option<HallStat> {
+    let channels = readings.first()?.len();
+    if readings.iter().any(|r| r.len() != channels) {
+        return None;
+    }
+    let n = readings.len() as f64;
+    let mut stat = HallStat {
+        samples: readings.len() as u32,
+        mean: Vec::with_capacity(channels),
+        std: Vec::with_capacity(channels),
+        min: Vec::with_capacity(channels),
+        max: Vec::with_capacity(channels),
+    };
+    for ch in 0..channels {
+        let values = readings.iter().map(|r| r[ch]);
+        let mean = values.clone().map(|v| v as f64).sum::<f64>() / n;
+        // 样本标准差，单次采样时为 0
+        let std = if readings.len() > 1 {
+            let var = values.clone().map(|v| (v as f64 - mean).powi(2)).sum::<f64>() / (n - 1.0);
+            var.sqrt()
+        } else {
+            0.0
+        };
+        stat.mean.push(mean as f32);
+        stat.std.push(std as f32);
+        stat.min.push(values.clone().min()?);
+        stat.max.push(values.max()?);
+    }
+    Some(stat)
+}
+
 pub fn laser_parse_data(frames: BTreeMap<u8, Vec<u8>>, angle: f32, laser_d: f32) -> Option<Vec<LaserData>> {
     let mut result = Vec::new();
 
@@ -68,7 +148,7 @@ pub fn laser_parse_data(frames: BTreeMap<u8, Vec<u8>>, angle: f32, laser_d: f32)
                 count += 1;
                 continue;
             }
-            println!("r={},z={}", r_tmp, z_tmp);
+            trace!("r={},z={}", r_tmp, z_tmp);
             let r = laser_d - 200.0_f32 - r_tmp;
             let z = z_tmp;
 
@@ -83,63 +163,203 @@ pub fn laser_parse_data(frames: BTreeMap<u8, Vec<u8>>, angle: f32, laser_d: f32)
     Some(result)
 }
 
+/// 枚举串口并补充 USB 信息，hall_id / motor_id 为自动检测时记住的设备标识
+pub fn list_ports(hall_id: Option<&UsbId>, motor_id: Option<&UsbId>) -> AppResult<Vec<PortInfo>> {
+    let ports = tokio_serial::available_ports().map_err(|e| AppError::State(format!("获取串口列表失败: {}", e)))?;
+    Ok(ports
+        .into_iter()
+        .map(|p| match p.port_type {
+            SerialPortType::UsbPort(info) => {
+                let device = if hall_id.is_some_and(|id| id.matches(&p.port_name, &info)) {
+                    Some(Device::Hall)
+                } else if motor_id.is_some_and(|id| id.matches(&p.port_name, &info)) {
+                    Some(Device::Motor)
+                } else {
+                    None
+                };
+                let product = info.product.clone().unwrap_or("Unknown Product".to_string());
+                PortInfo {
+                    port: p.port_name,
+                    info: match device {
+                        Some(d) => format!("{}（{}）", product, d.label()),
+                        None => product,
+                    },
+                    port_type: "usb".into(),
+                    vid: Some(info.vid),
+                    pid: Some(info.pid),
+                    serial_number: info.serial_number,
+                    manufacturer: info.manufacturer,
+                    product: info.product,
+                    interface: info.interface,
+                    device,
+                }
+            }
+            other => {
+                let (port_type, info) = match other {
+                    SerialPortType::PciPort => ("pci", "PCI Port"),
+                    SerialPortType::BluetoothPort => ("bluetooth", "Bluetooth Port"),
+                    _ => ("unknown", "串行设备"),
+                };
+                PortInfo {
+                    port: p.port_name,
+                    info: info.to_string(),
+                    port_type: port_type.into(),
+                    vid: None,
+                    pid: None,
+                    serial_number: None,
+                    manufacturer: None,
+                    product: None,
+                    interface: None,
+                    device: None,
+                }
+            }
+        })
+        .collect())
+}
+
 #[tauri::command]
-pub async fn get_port(_: tauri::State<'_, Arc<AppWrapper>>) -> Result<SerialPortList, String> {
-    let portlist = tokio_serial::available_ports();
-    match portlist {
-        Ok(ports) => {
-            let payload = SerialPortList {
-                port_vec: ports
-                    .into_iter()
-                    .map(|p| PortInfo {
-                        port: p.port_name.clone(),
-                        info: match p.port_type {
-                            SerialPortType::UsbPort(info) => {
-                                info.product.unwrap_or("Unknown Product".to_string())
-                            }
-                            SerialPortType::PciPort => "PCI Port".to_string(),
-                            SerialPortType::BluetoothPort => "Bluetooth Port".to_string(),
-                            SerialPortType::Unknown => "串行设备".to_string(),
-                        },
-                    })
-                    .collect(),
-            };
-            Ok(payload)
-        }
-        Err(e) => Err(format!("Failed to get serial port: {}", e)),
-    }
+pub async fn get_port(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<SerialPortList> {
+    let (hall_id, motor_id) = {
+        let settings = app.settings.lock().await;
+        (settings.hall_usb_id.clone(), settings.motor_usb_id.clone())
+    };
+    Ok(SerialPortList {
+        port_vec: list_ports(hall_id.as_ref(), motor_id.as_ref())?,
+    })
+}
+/// 自动识别各串口上连接的设备
+#[tauri::command]
+pub async fn probe_ports(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    remember: Option<bool>,
+) -> AppResult<Vec<ProbeResult>> {
+    device::probe_ports(&app, remember.unwrap_or(false)).await
+}
+
+/// 在局域网中查找激光扫描仪，broadcast 为额外的定向广播地址。
+/// 扫描仪不提供型号和序列号，返回的只有应答的 IP 和端口
+#[tauri::command]
+pub async fn discover_lasers(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    port: Option<u16>,
+    broadcast: Option<Vec<String>>,
+) -> AppResult<Vec<LaserScanner>> {
+    device::discover_lasers(&app, port, broadcast).await
 }
+
 #[tauri::command]
 pub async fn init_device(
     app: tauri::State<'_, Arc<AppWrapper>>,
-    hall_port: String,
-    motor_port: String,
-    laser_addr: String,
-) -> Result<String, String> {
+    hall_port: Option<String>,
+    motor_port: Option<String>,
+    laser_addr: Option<String>,
+    hall_serial: Option<SerialSettings>,
+    motor_serial: Option<SerialSettings>,
+    laser_bind: Option<LaserSettings>,
+) -> AppResult<String> {
     app
-        .init(&hall_port, &motor_port, laser_addr)
+        .init(hall_port, motor_port, laser_addr, hall_serial, motor_serial, laser_bind)
         .await
 }
 
 #[tauri::command]
-pub async fn deinit_device(app: tauri::State<'_, Arc<AppWrapper>>) -> Result<String, String> {
+pub async fn get_settings(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<AppSettings> {
+    Ok(app.settings.lock().await.clone())
+}
+
+#[tauri::command]
+pub async fn set_settings(app: tauri::State<'_, Arc<AppWrapper>>, settings: AppSettings) -> AppResult<String> {
+    app.set_settings(settings).await?;
+    Ok("设置已保存".into())
+}
+
+#[tauri::command]
+pub async fn deinit_device(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<String> {
     app.deinit().await
 }
 
 #[tauri::command]
-pub async fn get_hall(app: tauri::State<'_, Arc<AppWrapper>>) -> Result<(), String> {
+pub async fn connect_hall(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    port: String,
+    settings: Option<SerialSettings>,
+) -> AppResult<String> {
+    app.connect_hall(&port, settings).await?;
+    Ok(format!("霍尔传感器已连接到{}", port))
+}
+
+#[tauri::command]
+pub async fn connect_motor(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    port: String,
+    settings: Option<SerialSettings>,
+) -> AppResult<String> {
+    app.connect_motor(&port, settings).await?;
+    app.sync_motor_params().await;
+    app.restore_motor_speed().await;
+    Ok(format!("电机控制器已连接到{}", port))
+}
+
+/// 重新读取控制器脉冲参数并与设置核对
+#[tauri::command]
+pub async fn sync_motor_params(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<String> {
+    if !is_open(&app, Device::Motor).await {
+        return Err(AppError::Disconnected(Device::Motor));
+    }
+    Ok(app.sync_motor_params().await)
+}
+
+#[tauri::command]
+pub async fn connect_laser(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    laser_addr: String,
+    bind: Option<LaserSettings>,
+) -> AppResult<String> {
+    app.connect_laser(laser_addr.clone(), bind).await?;
+    Ok(format!("激光传感器已连接到{}", laser_addr))
+}
+
+#[tauri::command]
+pub async fn disconnect_hall(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<String> {
+    app.disconnect_device(Device::Hall).await;
+    Ok("霍尔传感器已断开".into())
+}
+
+#[tauri::command]
+pub async fn disconnect_motor(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<String> {
+    app.disconnect_device(Device::Motor).await;
+    Ok("电机控制器已断开".into())
+}
+
+#[tauri::command]
+pub async fn disconnect_laser(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<String> {
+    app.disconnect_device(Device::Laser).await;
+    Ok("激光传感器已断开".into())
+}
+
+#[tauri::command]
+pub async fn get_device_status(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<DeviceStatusList> {
+    Ok(DeviceStatusList {
+        hall: device_state(&app, Device::Hall).await,
+        motor: device_state(&app, Device::Motor).await,
+        laser: device_state(&app, Device::Laser).await,
+    })
+}
+
+#[tauri::command]
+pub async fn get_hall(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<()> {
     app.get_hall_data().await?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn rotate_motor(app: tauri::State<'_, Arc<AppWrapper>>) -> Result<(), String> {
+pub async fn rotate_motor(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<()> {
     app.rotate_motor_step().await?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn get_laser(app: tauri::State<'_, Arc<AppWrapper>>) -> Result<(), String> {
+pub async fn get_laser(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<()> {
     app.get_laser_data().await?;
     Ok(())
 }
@@ -148,7 +368,7 @@ pub async fn get_laser(app: tauri::State<'_, Arc<AppWrapper>>) -> Result<(), Str
 pub async fn set_motor_speed(
     app: tauri::State<'_, Arc<AppWrapper>>,
     speed: f32,
-) -> Result<String, String> {
+) -> AppResult<String> {
     app.set_motor_speed(speed).await
 }
 
@@ -156,195 +376,428 @@ pub async fn set_motor_speed(
 pub async fn set_motor_single_angle(
     app: tauri::State<'_, Arc<AppWrapper>>,
     angle: f32,
-) -> Result<String, String> {
+) -> AppResult<String> {
     app.set_motor_single_angle(angle).await
 }
 #[tauri::command]
 pub async fn get_motor_angle(
     app: tauri::State<'_, Arc<AppWrapper>>,
-) -> Result<f32, String> {
+) -> AppResult<f32> {
     app.get_motor_angle().await
 }
 
 #[tauri::command]
 pub async fn set_motor_calibrated(
     app: tauri::State<'_, Arc<AppWrapper>>,
-) -> Result<String, String> {
+) -> AppResult<String> {
     app.set_motor_calibrated().await
 }
 #[tauri::command]
-pub async fn motor_start_one_circle(app: tauri::State<'_, Arc<AppWrapper>>) -> Result<(), String> {
+pub async fn motor_start_one_circle(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<()> {
     app.rotate_motor_pulse(*app.single_circle_pulse.lock().await).await
 }
 #[tauri::command]
-pub async fn motor_start_u(app: tauri::State<'_, Arc<AppWrapper>>) -> Result<(), String> {
+pub async fn motor_start_u(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<()> {
     app.motor_start_u().await
 }
 #[tauri::command]
-pub async fn motor_start_d(app: tauri::State<'_, Arc<AppWrapper>>) -> Result<(), String> {
+pub async fn motor_start_d(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<()> {
     app.motor_start_d().await
 }
 
 #[tauri::command]
-pub async fn motor_stop(app: tauri::State<'_, Arc<AppWrapper>>) -> Result<(), String> {
+pub async fn motor_stop(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<()> {
     app.motor_stop().await
 }
+/// 自动回零，homing 不为空时先保存新的回零参数
+#[tauri::command]
+pub async fn home_motor(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    homing: Option<HomingSettings>,
+) -> AppResult<String> {
+    if let Some(homing) = homing {
+        app.update_settings(|s| s.homing = homing).await?;
+    }
+    if !is_open(&app, Device::Motor).await {
+        return Err(AppError::Disconnected(Device::Motor));
+    }
+    let sensor = match app.settings.lock().await.homing.source {
+        HomingSource::Hall => Device::Hall,
+        HomingSource::Laser => Device::Laser,
+    };
+    if !is_open(&app, sensor).await {
+        return Err(AppError::Disconnected(sensor));
+    }
+    motion::home_motor(&app).await
+}
+
+/// 转到绝对角度，默认容差 0.1°、最多重试 3 次
 #[tauri::command]
-pub fn stop_work(app: tauri::State<'_, Arc<AppWrapper>>) -> Result<String, String> {
-    app.stop_tx.send(true).map_err(|e| e.to_string())?;
+pub async fn move_to_angle(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    angle: f32,
+    tolerance: Option<f32>,
+    retries: Option<u32>,
+) -> AppResult<MoveResult> {
+    motion::move_to_angle(&app, angle, tolerance.unwrap_or(0.1), retries.unwrap_or(3)).await
+}
+
+/// 保存刀具类型的运动曲线，apply 为真时切换到该刀具并写入控制器
+#[tauri::command]
+pub async fn set_motion_profile(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    cutter_type: String,
+    profile: MotionProfile,
+    apply: Option<bool>,
+) -> AppResult<String> {
+    let cutter_type = cutter_type.trim().to_string();
+    app.update_settings(|s| {
+        s.motion_profiles.insert(cutter_type.clone(), profile);
+    })
+    .await?;
+    if apply.unwrap_or(false) {
+        return apply_motion_profile(app, Some(cutter_type)).await;
+    }
+    Ok(format!("已保存{}的运动曲线", cutter_type))
+}
+
+/// 将刀具类型的运动曲线写入控制器并设为当前刀具，未指定时使用当前刀具类型
+#[tauri::command]
+pub async fn apply_motion_profile(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    cutter_type: Option<String>,
+) -> AppResult<String> {
+    let (cutter_type, profile) = {
+        let settings = app.settings.lock().await;
+        let cutter_type = cutter_type.unwrap_or_else(|| settings.cutter_type.clone());
+        let profile = settings
+            .motion_profiles
+            .get(&cutter_type)
+            .cloned()
+            .ok_or_else(|| AppError::InvalidInput(format!("没有刀具类型{}的运动曲线", cutter_type)))?;
+        (cutter_type, profile)
+    };
+    let detail = motion::apply_motion_profile(&app, &profile).await?;
+    app.update_settings(|s| s.cutter_type = cutter_type.clone()).await?;
+    Ok(format!("已应用{}的运动曲线，{}", cutter_type, detail))
+}
+
+#[tauri::command]
+pub fn stop_work(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<String> {
+    app.stop_tx
+        .send(true)
+        .map_err(|_| AppError::State("当前没有正在进行的采集任务".into()))?;
     Ok("Background task stopping...".into())
 }
 
 #[tauri::command]
-pub async fn set_motor_single_circle_pulse(app: tauri::State<'_, Arc<AppWrapper>>, pulse: u32) -> Result<String, String> {
+pub async fn set_motor_single_circle_pulse(app: tauri::State<'_, Arc<AppWrapper>>, pulse: u32) -> AppResult<String> {
     app.set_single_circle_pulse(pulse).await
 }
 
 
+/// 为本次检测开始抓包，失败只记录日志，不影响检测
+fn start_session_capture(app: &AppWrapper, parent_id: i64) -> Option<PathBuf> {
+    let path = capture_dir(&app.app_handler).map(|dir| dir.join(capture_file_name(&format!("project-{}", parent_id))));
+    // 已有手动开始的记录时继续写入原文件，不替换也不在检测结束时关闭
+    match path.and_then(|p| app.capture.start_if_idle(p.clone()).map(|started| started.then_some(p))) {
+        Ok(Some(path)) => Some(path),
+        Ok(None) => {
+            info!("Manual capture running, session capture not started");
+            None
+        }
+        Err(e) => {
+            warn!("Failed to start capture: {}", e);
+            None
+        }
+    }
+}
+
 #[tauri::command]
 pub async fn start_work(
     app: tauri::State<'_, Arc<AppWrapper>>,
     name: String,
-    laser_path: String,
-    hall_path: String,
-    v_path: String,
+    laser_path: Option<String>,
+    hall_path: Option<String>,
+    v_path: Option<String>,
     hall_d: f32,
     laser_d: f32,
-) -> Result<String, String> {
+    samples: Option<u32>,
+    max_std: Option<f32>,
+    use_hall: Option<bool>,
+    use_laser: Option<bool>,
+) -> AppResult<String> {
+    // 每个角度的霍尔采样次数，默认单次采样
+    let samples = samples.unwrap_or(1);
+    if !(1..=MAX_HALL_SAMPLES).contains(&samples) {
+        return Err(AppError::InvalidInput(format!("每个角度的采样次数必须为1~{}", MAX_HALL_SAMPLES)));
+    }
+    if max_std.is_some_and(|s| !s.is_finite() || s <= 0.0) {
+        return Err(AppError::InvalidInput("标准差阈值必须大于0".into()));
+    }
+    // 未指定时两种传感器都参与采集
+    let use_hall = use_hall.unwrap_or(true);
+    let use_laser = use_laser.unwrap_or(true);
+    if !use_hall && !use_laser {
+        return Err(AppError::InvalidInput("至少需要启用一种传感器".into()));
+    }
+    if !is_open(&app, Device::Motor).await {
+        return Err(AppError::Disconnected(Device::Motor));
+    }
+    if use_hall && !is_open(&app, Device::Hall).await {
+        return Err(AppError::Disconnected(Device::Hall));
+    }
+    if use_laser && !is_open(&app, Device::Laser).await {
+        return Err(AppError::Disconnected(Device::Laser));
+    }
+    // 未指定保存路径时沿用上次的设置
+    let output = app.settings.lock().await.output.clone();
+    let hall_path = hall_path.or(output.hall_path);
+    let v_path = v_path.or(output.v_path);
+    let laser_path = laser_path.or(output.laser_path);
+    let hall_paths = match (use_hall, hall_path, v_path) {
+        (false, _, _) => None,
+        (true, Some(h), Some(v)) => Some((h, v)),
+        _ => return Err(AppError::InvalidInput("未指定霍尔数据文件".into())),
+    };
+    let laser_path = match (use_laser, laser_path) {
+        (false, _) => None,
+        (true, Some(l)) => Some(l),
+        _ => return Err(AppError::InvalidInput("未指定激光数据文件".into())),
+    };
+    // 先占用设备，此后健康监测不再向设备发送探测命令
+    if !device::claim(&app, &app.scanning).await {
+        return Err(AppError::State("采集进行中".into()));
+    }
+    if app.moving.load(Ordering::SeqCst) {
+        app.scanning.store(false, Ordering::SeqCst);
+        return Err(AppError::State("电机回零或定位中，无法开始采集".into()));
+    }
+    app.remember_settings(|s| {
+        s.hall_d = hall_d;
+        s.laser_d = laser_d;
+        if let Some((h, v)) = &hall_paths {
+            s.output.hall_path = Some(h.clone());
+            s.output.v_path = Some(v.clone());
+        }
+        if let Some(l) = &laser_path {
+            s.output.laser_path = Some(l.clone());
+        }
+    })
+    .await;
     // Arc<Mutex<AppWrapper>>
     let _ = app.stop_tx.send(false);
     // 创建一个停止信号 channel
     let stop_rx = app.stop_tx.subscribe();
     let app = Arc::clone(&app);
-    app.motor_start_work().await?;
-    // 启动监听任务（只启动一次即可）
-    app.clone().spawn_motor_listener().await;
-    let parent_id = match create_project(name.clone(), hall_d, laser_d) {
+    let parent_id = match create_project(name.clone(), PROJECT_SCAN, hall_d, laser_d) {
         Ok(id) => id,
-        Err(_e) => {
-            return Err("数据库异常！".into());
+        Err(e) => {
+            app.scanning.store(false, Ordering::SeqCst);
+            return Err(e);
         }
     };
-    tokio::spawn(async move {
-        let mut lock = app.motor_rx.lock().await;
-        let mut stop_rx = stop_rx; // mutable
-        let mut laser_file = OpenOptions::new()
-            .create(true) // 文件不存在就创建
-            .append(true) // 追加而不是覆盖
-            .open(laser_path)
-            .await
-            .expect("无法打开文件");
-        let mut hall_file = OpenOptions::new()
-            .create(true) // 文件不存在就创建
-            .append(true) // 追加而不是覆盖
-            .open(hall_path)
-            .await
-            .expect("无法打开文件");
-        let mut v_file = OpenOptions::new()
-            .create(true) // 文件不存在就创建
-            .append(true) // 追加而不是覆盖
-            .open(v_path)
-            .await
-            .expect("无法打开文件");
-        loop {
-            tokio::select! {
-                angle = lock.recv() => {
-                    match angle {
-                        Some(a) => {
-                            let hall_data = app.get_hall_data().await;
-                            let laser_data = app.get_laser_data().await;
-                            match hall_data {
-                                Ok(data) => {
-                                    match insert_data(parent_id, a, &data) {
-                                        Ok(_) => {
-                                            let mut v_array: Vec<f32> = Vec::new();
-                                            for hall_datum in &data {
-                                                let v = (1650_f32 * (*hall_datum as f32) / 8388607_f32) / 64_f32;
-                                                v_array.push(v);
-                                            }
-                                            let v_line = format!("{} {} {} {} {} {} {} {} {} {}\n",
-                                                                 a,
-                                                                 v_array[0],
-                                                                 v_array[1],
-                                                                 v_array[2],
-                                                                 v_array[3],
-                                                                 v_array[4],
-                                                                 v_array[5],
-                                                                 v_array[6],
-                                                                 v_array[7],
-                                                                 v_array[8],
-                                            );
-                                            v_file.write_all(v_line.as_bytes()).await.expect("写入失败");
-                                            let line = format!(" {} {} {} {} {} {} {} {} {} {}\n",
-                                                               a,
-                                                               data[0],
-                                                               data[1],
-                                                               data[2],
-                                                               data[3],
-                                                               data[4],
-                                                               data[5],
-                                                               data[6],
-                                                               data[7],
-                                                               data[8],
-                                            );
-                                            hall_file.write_all(line.as_bytes()).await.expect("写入失败");
-                                            app.push_hall_data(Payload { angle:a, data }).await;
-                                        }
-                                        Err(e) => {
-                                            app.app_handler.emit("message", MessagePayload {
-                                                title: "霍尔传感器异常".to_string(),
-                                                message: e,
-                                                _type: "error".to_string(),
-                                            }).unwrap();
-                                        }
-                                    }
-                                }
-
-                                Err(e) => {
-                                    eprintln!("Error getting hall data: {}", e);
-                                    app.app_handler.emit("message", MessagePayload {
-                                        title: "霍尔传感器异常".to_string(),
-                                        message: e,
-                                        _type: "error".to_string(),
-                                    }).unwrap();
-                                    return;
-                                }
-                            }
-                            match laser_data {
-                                Ok(data) => {
-                                    if let Some(data) = laser_parse_data(data, a, laser_d) {
-                                        for datum in data {
-                                            let line = format!("{} {} {}\n", datum.x, datum.y, datum.z);
-                                            laser_file.write_all(line.as_bytes()).await.expect("写入失败");
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    eprintln!("Error getting laser data: {}", e);
-                                    return;
-                                }
-                            }
-                            println!("Angle={}", a);
-                            // TODO: 拿传感器数据并存储
-                        }
-                        None => break, // channel 关闭
-                    }
-                }
-
-                _ = stop_rx.changed() => {
-                    if *stop_rx.borrow() {
-                        println!("Motor listener stopping...");
-                        break;
-                    }
-                }
+    let capture_traffic = app.settings.lock().await.capture_traffic;
+    let capture = if capture_traffic { start_session_capture(&app, parent_id) } else { None };
+    let config = ScanConfig {
+        parent_id,
+        samples,
+        max_std,
+        laser_d,
+        hall_paths,
+        laser_path,
+        capture,
+    };
+    // 先订阅再发送开始检测命令，应答前上报的角度也能收到
+    let events = app.motor_events.subscribe();
+    // 先打开输出文件和启动电机，失败时直接返回错误并标记项目中止
+    let started = match ScanSink::open(app.clone(), &config).await {
+        Ok(sink) => app.motor_start_work().await.map(|_| sink),
+        Err(e) => Err(e),
+    };
+    let sink = match started {
+        Ok(sink) => sink,
+        Err(e) => {
+            let _ = set_project_status(parent_id, PROJECT_ABORTED);
+            if let Some(path) = &config.capture {
+                app.capture.stop_file(path);
             }
+            app.scanning.store(false, Ordering::SeqCst);
+            return Err(e);
         }
-    });
+    };
+    // 每次检测启动新的监听任务和角度通道
+    let motor_rx = app.clone().spawn_motor_listener(events).await;
+    spawn_scan(app, config, sink, motor_rx, stop_rx);
 
     Ok("任务已启动".into())
 }
 
+/// 回放已保存的项目或数据文件，speed 默认按原始速度回放
+#[tauri::command]
+pub async fn start_replay(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    source: ReplaySource,
+    speed: Option<f32>,
+) -> AppResult<i64> {
+    replay::start_replay(Arc::clone(&app), source, speed.unwrap_or(1.0)).await
+}
+
+/// 开始记录设备收发数据，未指定路径时写入日志目录，返回抓包文件路径
+#[tauri::command]
+pub fn start_capture(app: tauri::State<'_, Arc<AppWrapper>>, path: Option<String>) -> AppResult<String> {
+    let path = match path {
+        Some(p) => PathBuf::from(p),
+        None => capture_dir(&app.app_handler)?.join(capture_file_name("manual")),
+    };
+    app.capture.start(path.clone())?;
+    Ok(path.display().to_string())
+}
+
+/// 停止记录，返回抓包文件路径
+#[tauri::command]
+pub fn stop_capture(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<String> {
+    match app.capture.stop() {
+        Some(path) => Ok(path.display().to_string()),
+        None => Err(AppError::State("当前没有在记录设备数据".into())),
+    }
+}
+
+#[tauri::command]
+pub fn decode_capture(path: String) -> AppResult<Vec<CaptureRecord>> {
+    capture::decode_file(Path::new(&path))
+}
+
+/// 修改日志级别，module 为空时修改默认级别
+#[tauri::command]
+pub async fn set_log_level(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    module: Option<String>,
+    level: String,
+) -> AppResult<String> {
+    app.set_log_level(module, level).await?;
+    Ok("日志级别已修改".into())
+}
+
+/// 最近的日志，level 为最低级别，默认返回全部级别
+#[tauri::command]
+pub fn fetch_logs(max: Option<usize>, level: Option<String>) -> AppResult<Vec<LogLine>> {
+    logging::recent(max.unwrap_or(500), level.as_deref())
+}
+
+#[tauri::command]
+pub async fn fetch_hall_data(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    consumer: Option<String>,
+) -> AppResult<Vec<Sequenced<Payload>>> {
+    let consumer = consumer.unwrap_or_else(|| "default".into());
+    Ok(app.fetch_hall_data(&consumer, 1000).await)
+}
+
+/// 读取序号 since 之后的霍尔数据，lost 非零表示部分数据已被覆盖
+#[tauri::command]
+pub async fn fetch_hall_since(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    since: u64,
+    max: Option<usize>,
+) -> AppResult<RingSlice<Payload>> {
+    Ok(app.fetch_hall_since(since, max.unwrap_or(1000)).await)
+}
+
+/// 按 laser_profile 事件中的序号取激光轮廓，未指定序号时取最新的，尚未采集或已被覆盖时为空
+#[tauri::command]
+pub async fn get_laser_profile(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    seq: Option<u64>,
+) -> AppResult<Option<Sequenced<LaserProfile>>> {
+    Ok(app.laser_profile(seq).await)
+}
+
+/// 角度范围内的激光轮廓，start 大于 end 时表示跨过 0°，(0, 360) 为整圈
+#[tauri::command]
+pub async fn fetch_laser_profiles(
+    app: tauri::State<'_, Arc<AppWrapper>>,
+    start: f32,
+    end: f32,
+) -> AppResult<Vec<Sequenced<LaserProfile>>> {
+    if !start.is_finite() || !end.is_finite() {
+        return Err(AppError::InvalidInput("角度范围不合法".into()));
+    }
+    Ok(app.laser_profiles_in_range(start, end).await)
+}
+
+/// 最近一圈的霍尔数据
 #[tauri::command]
-pub async fn fetch_hall_data(app: tauri::State<'_, Arc<AppWrapper>>) -> Result<Vec<Payload>, String> {
-    Ok(app.fetch_hall_data(1000).await)
+pub async fn hall_last_revolution(app: tauri::State<'_, Arc<AppWrapper>>) -> AppResult<Vec<Sequenced<Payload>>> {
+    Ok(app.hall_last_revolution().await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hall_frame(values: [i32; 9]) -> Vec<u8> {
+        let mut frame = vec![0xFF, 0xEE, 0xAA, 0xEF];
+        for v in values {
+            frame.extend_from_slice(&v.to_le_bytes());
+        }
+        frame.extend_from_slice(&[0xEF, 0xFE, 0xFF, 0xEE]);
+        frame
+    }
+
+    #[test]
+    fn hall_frame_accepts_adc_readings() {
+        let frame = hall_frame([0, 1, -1, 8388607, -8388608, 100, -100, 4096, -4096]);
+        assert!(hall_frame_valid(&frame));
+        assert_eq!(hall_parse_data(&frame).unwrap()[3], 8388607);
+    }
+
+    #[test]
+    fn hall_frame_rejects_wrong_length() {
+        let frame = hall_frame([0; 9]);
+        assert!(!hall_frame_valid(&frame[..43]));
+        let mut longer = frame.clone();
+        longer.push(0);
+        assert!(!hall_frame_valid(&longer));
+    }
+
+    #[test]
+    fn hall_frame_rejects_out_of_range_readings() {
+        assert!(!hall_frame_valid(&hall_frame([0, 0, 0, 0, 8388608, 0, 0, 0, 0])));
+        assert!(!hall_frame_valid(&[0x41; 44]));
+    }
+
+    #[test]
+    fn statistics_of_single_reading() {
+        let stat = hall_statistics(&[vec![1, -2, 3]]).unwrap();
+        assert_eq!(stat.samples, 1);
+        assert_eq!(stat.mean, vec![1.0, -2.0, 3.0]);
+        assert_eq!(stat.std, vec![0.0, 0.0, 0.0]);
+        assert_eq!(stat.min, vec![1, -2, 3]);
+        assert_eq!(stat.max, vec![1, -2, 3]);
+    }
+
+    #[test]
+    fn statistics_per_channel() {
+        let stat = hall_statistics(&[vec![2, 10], vec![4, 10], vec![6, 10]]).unwrap();
+        assert_eq!(stat.samples, 3);
+        assert_eq!(stat.mean, vec![4.0, 10.0]);
+        // 样本标准差：sqrt((4 + 0 + 4) / 2)
+        assert_eq!(stat.std, vec![2.0, 0.0]);
+        assert_eq!(stat.min, vec![2, 10]);
+        assert_eq!(stat.max, vec![6, 10]);
+        assert_eq!(stat.max_std(), 2.0);
+    }
+
+    #[test]
+    fn statistics_rejects_empty_and_ragged_input() {
+        assert!(hall_statistics(&[]).is_none());
+        assert!(hall_statistics(&[vec![1, 2], vec![1]]).is_none());
+    }
+
+    #[test]
+    fn rounded_mean_rounds_half_away_from_zero() {
+        let stat = hall_statistics(&[vec![1, -1], vec![2, -2]]).unwrap();
+        assert_eq!(stat.rounded_mean(), vec![2, -2]);
+    }
 }
@@ -0,0 +1,772 @@
+use crate::error::{AppError, AppResult, Device};
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio_serial::{DataBits, FlowControl, Parity, SerialPortBuilder, StopBits, UsbPortInfo};
+
+const SETTINGS_FILE: &str = "settings.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParitySetting {
+    None,
+    Odd,
+    Even,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlowControlSetting {
+    None,
+    Software,
+    Hardware,
+}
+
+/// 单个串口的通信参数
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SerialSettings {
+    pub baud_rate: u32,
+    pub data_bits: u8,
+    pub parity: ParitySetting,
+    pub stop_bits: u8,
+    pub flow_control: FlowControlSetting,
+    /// 等待设备响应的超时时间
+    pub timeout_ms: u64,
+}
+
+impl Default for SerialSettings {
+    fn default() -> Self {
+        SerialSettings {
+            baud_rate: 115200,
+            data_bits: 8,
+            parity: ParitySetting::None,
+            stop_bits: 1,
+            flow_control: FlowControlSetting::None,
+            timeout_ms: 1000,
+        }
+    }
+}
+
+impl SerialSettings {
+    pub fn validate(&self) -> AppResult<()> {
+        if !(300..=4_000_000).contains(&self.baud_rate) {
+            return Err(AppError::InvalidInput(format!("波特率{}超出范围", self.baud_rate)));
+        }
+        if !(5..=8).contains(&self.data_bits) {
+            return Err(AppError::InvalidInput(format!("数据位必须为5~8，当前为{}", self.data_bits)));
+        }
+        if self.stop_bits != 1 && self.stop_bits != 2 {
+            return Err(AppError::InvalidInput(format!("停止位必须为1或2，当前为{}", self.stop_bits)));
+        }
+        if !(10..=60_000).contains(&self.timeout_ms) {
+            return Err(AppError::InvalidInput(format!("超时时间{}ms超出范围", self.timeout_ms)));
+        }
+        Ok(())
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    pub fn builder(&self, port: &str) -> SerialPortBuilder {
+        let data_bits = match self.data_bits {
+            5 => DataBits::Five,
+            6 => DataBits::Six,
+            7 => DataBits::Seven,
+            _ => DataBits::Eight,
+        };
+        let parity = match self.parity {
+            ParitySetting::None => Parity::None,
+            ParitySetting::Odd => Parity::Odd,
+            ParitySetting::Even => Parity::Even,
+        };
+        let stop_bits = match self.stop_bits {
+            2 => StopBits::Two,
+            _ => StopBits::One,
+        };
+        let flow_control = match self.flow_control {
+            FlowControlSetting::None => FlowControl::None,
+            FlowControlSetting::Software => FlowControl::Software,
+            FlowControlSetting::Hardware => FlowControl::Hardware,
+        };
+        tokio_serial::new(port, self.baud_rate)
+            .data_bits(data_bits)
+            .parity(parity)
+            .stop_bits(stop_bits)
+            .flow_control(flow_control)
+            .timeout(self.timeout())
+    }
+}
+
+/// 激光 UDP 套接字的本地绑定参数
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaserSettings {
+    /// 本机网卡地址，多网卡时指定与激光同网段的地址
+    pub bind_addr: String,
+    /// 本地端口，0 表示由系统分配
+    pub bind_port: u16,
+    /// 端口被占用时改用系统分配的端口，需要激光按来源端口回包
+    pub allow_ephemeral: bool,
+}
+
+impl Default for LaserSettings {
+    fn default() -> Self {
+        LaserSettings {
+            bind_addr: "0.0.0.0".into(),
+            bind_port: 43000,
+            allow_ephemeral: false,
+        }
+    }
+}
+
+impl LaserSettings {
+    pub fn validate(&self) -> AppResult<()> {
+        self.bind_ip().map(|_| ())
+    }
+
+    fn bind_ip(&self) -> AppResult<IpAddr> {
+        self.bind_addr
+            .trim()
+            .parse()
+            .map_err(|_| AppError::InvalidInput(format!("本地地址{}格式不正确", self.bind_addr)))
+    }
+
+    pub fn socket_addr(&self) -> AppResult<SocketAddr> {
+        Ok(SocketAddr::new(self.bind_ip()?, self.bind_port))
+    }
+
+    pub fn ephemeral_addr(&self) -> AppResult<SocketAddr> {
+        Ok(SocketAddr::new(self.bind_ip()?, 0))
+    }
+}
+
+/// USB 串口的标识，厂商号和产品号相同的设备再按序列号区分，没有序列号时按端口路径区分
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UsbId {
+    pub vid: u16,
+    pub pid: u16,
+    #[serde(default)]
+    pub serial_number: Option<String>,
+    /// 检测时的端口路径，仅在设备没有序列号时记录
+    #[serde(default)]
+    pub port: Option<String>,
+}
+
+impl UsbId {
+    pub fn of(port: &str, info: &UsbPortInfo) -> Self {
+        UsbId {
+            vid: info.vid,
+            pid: info.pid,
+            serial_number: info.serial_number.clone(),
+            port: info.serial_number.is_none().then(|| port.to_string()),
+        }
+    }
+
+    /// 是否为同一设备，旧版本只记录了 VID/PID 的条目无法区分同型号设备，需要重新检测
+    pub fn matches(&self, port: &str, info: &UsbPortInfo) -> bool {
+        if self.vid != info.vid || self.pid != info.pid {
+            return false;
+        }
+        match (&self.serial_number, &self.port) {
+            (Some(sn), _) => info.serial_number.as_ref() == Some(sn),
+            (None, Some(p)) => p == port,
+            (None, None) => false,
+        }
+    }
+}
+
+/// 电机脉冲与转速参数
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MotorSettings {
+    /// 单步脉冲个数
+    pub step_pulse: u32,
+    /// 单圈脉冲个数
+    pub single_circle_pulse: u32,
+    /// 转速，单位 RPM
+    pub speed: f32,
+}
+
+impl Default for MotorSettings {
+    fn default() -> Self {
+        MotorSettings {
+            step_pulse: 40,
+            single_circle_pulse: 15000,
+            speed: 1.0,
+        }
+    }
+}
+
+impl MotorSettings {
+    pub fn validate(&self) -> AppResult<()> {
+        if self.single_circle_pulse == 0 {
+            return Err(AppError::InvalidInput("单圈脉冲个数必须大于0".into()));
+        }
+        if self.step_pulse == 0 || self.step_pulse > self.single_circle_pulse {
+            return Err(AppError::InvalidInput(format!(
+                "单步脉冲个数必须在1~{}之间",
+                self.single_circle_pulse
+            )));
+        }
+        if !self.speed.is_finite() || self.speed <= 0.0 || self.speed > 600.0 {
+            return Err(AppError::InvalidInput(format!("转速{}RPM超出范围", self.speed)));
+        }
+        Ok(())
+    }
+}
+
+/// 连接电机时控制器脉冲参数与设置不一致的处理方式
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PulseSyncPolicy {
+    /// 以控制器中的参数为准并更新设置
+    Controller,
+    /// 将设置中的参数写入控制器
+    Settings,
+}
+
+/// 回零时检测参考特征所用的传感器
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HomingSource {
+    Hall,
+    Laser,
+}
+
+/// 回零点动方向，分别对应电机命令 6 和 7
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HomingDirection {
+    Up,
+    Down,
+}
+
+impl HomingDirection {
+    pub fn reverse(self) -> Self {
+        match self {
+            HomingDirection::Up => HomingDirection::Down,
+            HomingDirection::Down => HomingDirection::Up,
+        }
+    }
+}
+
+/// 自动回零参数
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HomingSettings {
+    pub source: HomingSource,
+    /// 霍尔通道序号，从 0 开始
+    pub channel: usize,
+    /// 读数相对起始值变化超过该值即认为到达参考特征
+    pub threshold: f32,
+    pub direction: HomingDirection,
+    pub timeout_s: u64,
+    /// 点动过程中的采样间隔
+    pub poll_ms: u64,
+    /// 第二次慢速逼近参考特征时的转速，单位 RPM
+    pub slow_speed: f32,
+    /// 回退到参考信号消失后继续回退的时间
+    pub back_off_ms: u64,
+}
+
+impl Default for HomingSettings {
+    fn default() -> Self {
+        HomingSettings {
+            source: HomingSource::Hall,
+            channel: 0,
+            threshold: 500.0,
+            direction: HomingDirection::Up,
+            timeout_s: 60,
+            poll_ms: 50,
+            slow_speed: 0.2,
+            back_off_ms: 500,
+        }
+    }
+}
+
+impl HomingSettings {
+    pub fn validate(&self) -> AppResult<()> {
+        if self.channel >= 9 {
+            return Err(AppError::InvalidInput(format!("霍尔通道必须为1~9，当前为{}", self.channel + 1)));
+        }
+        if !self.threshold.is_finite() || self.threshold <= 0.0 {
+            return Err(AppError::InvalidInput("回零阈值必须大于0".into()));
+        }
+        if !(1..=600).contains(&self.timeout_s) {
+            return Err(AppError::InvalidInput(format!("回零超时{}秒超出范围", self.timeout_s)));
+        }
+        if !(10..=5000).contains(&self.poll_ms) {
+            return Err(AppError::InvalidInput(format!("采样间隔{}ms超出范围", self.poll_ms)));
+        }
+        if !self.slow_speed.is_finite() || self.slow_speed <= 0.0 || self.slow_speed > 600.0 {
+            return Err(AppError::InvalidInput(format!("慢速逼近转速{}RPM超出范围", self.slow_speed)));
+        }
+        if self.back_off_ms > 10_000 {
+            return Err(AppError::InvalidInput(format!("回退时间{}ms超出范围", self.back_off_ms)));
+        }
+        Ok(())
+    }
+}
+
+/// 运动曲线，速度单位 RPM，加速度单位 RPM/s，加加速度单位 RPM/s²
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MotionProfile {
+    pub max_speed: f32,
+    pub accel: f32,
+    pub decel: f32,
+    /// 为 0 时使用梯形加减速，大于 0 时使用 S 形曲线
+    pub jerk: f32,
+    /// 每步到位后的稳定等待时间
+    pub settle_ms: u32,
+}
+
+impl Default for MotionProfile {
+    fn default() -> Self {
+        MotionProfile {
+            max_speed: 60.0,
+            accel: 120.0,
+            decel: 120.0,
+            jerk: 0.0,
+            settle_ms: 0,
+        }
+    }
+}
+
+impl MotionProfile {
+    pub fn validate(&self) -> AppResult<()> {
+        if !self.max_speed.is_finite() || self.max_speed <= 0.0 || self.max_speed > 600.0 {
+            return Err(AppError::InvalidInput(format!("最大转速{}RPM超出范围(0~600]", self.max_speed)));
+        }
+        for (name, value) in [("加速度", self.accel), ("减速度", self.decel)] {
+            if !value.is_finite() || value <= 0.0 || value > 10_000.0 {
+                return Err(AppError::InvalidInput(format!("{}{}RPM/s超出范围(0~10000]", name, value)));
+            }
+        }
+        if !self.jerk.is_finite() || self.jerk < 0.0 || self.jerk > 100_000.0 {
+            return Err(AppError::InvalidInput(format!("加加速度{}RPM/s²超出范围[0~100000]", self.jerk)));
+        }
+        if self.settle_ms > 5000 {
+            return Err(AppError::InvalidInput(format!("稳定时间{}ms超出范围", self.settle_ms)));
+        }
+        Ok(())
+    }
+}
+
+pub const DEFAULT_CUTTER_TYPE: &str = "默认";
+
+/// 刀具磨损限值，未设置的项不参与判定
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WearLimits {
+    /// 新刀的刃口半径，单位 mm，径向磨损量为名义半径减去测得的刃口半径
+    pub nominal_radius: Option<f32>,
+    /// 最大径向磨损，单位 mm，需要设置名义半径
+    pub max_radial_wear: Option<f32>,
+    /// 最大径向跳动，单位 mm
+    pub max_radial_runout: Option<f32>,
+    /// 刃口最大平台宽度，单位 mm
+    pub max_edge_flattening: Option<f32>,
+    /// 霍尔电压相对整圈均值的最大偏差
+    pub max_hall_deviation: Option<f32>,
+}
+
+impl WearLimits {
+    pub fn is_empty(&self) -> bool {
+        self.max_radial_wear.is_none()
+            && self.max_radial_runout.is_none()
+            && self.max_edge_flattening.is_none()
+            && self.max_hall_deviation.is_none()
+    }
+
+    pub fn validate(&self) -> AppResult<()> {
+        for (name, limit) in [
+            ("径向磨损", self.max_radial_wear),
+            ("径向跳动", self.max_radial_runout),
+            ("刃口平台宽度", self.max_edge_flattening),
+            ("霍尔偏差", self.max_hall_deviation),
+        ] {
+            if let Some(v) = limit {
+                if !v.is_finite() || v <= 0.0 {
+                    return Err(AppError::InvalidInput(format!("{}限值{}必须大于0", name, v)));
+                }
+            }
+        }
+        if let Some(r) = self.nominal_radius.filter(|r| !r.is_finite() || *r <= 0.0) {
+            return Err(AppError::InvalidInput(format!("名义半径{}必须大于0", r)));
+        }
+        if self.max_radial_wear.is_some() && self.nominal_radius.is_none() {
+            return Err(AppError::InvalidInput("设置径向磨损限值时必须设置名义半径".into()));
+        }
+        Ok(())
+    }
+}
+
+/// 日志级别，取值 off / error / warn / info / debug / trace
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogSettings {
+    pub level: String,
+    /// 按模块单独设置的级别，键为模块路径，例如 sqlite、acquisition 或 tauri
+    pub modules: BTreeMap<String, String>,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings {
+            level: "info".into(),
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+impl LogSettings {
+    pub fn validate(&self) -> AppResult<()> {
+        for level in std::iter::once(&self.level).chain(self.modules.values()) {
+            if level.parse::<log::LevelFilter>().is_err() {
+                return Err(AppError::InvalidInput(format!("日志级别{}不合法", level)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 检测数据的默认保存路径
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputSettings {
+    pub laser_path: Option<String>,
+    pub hall_path: Option<String>,
+    pub v_path: Option<String>,
+}
+
+/// 持久化到应用配置目录的设置
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub hall_serial: SerialSettings,
+    pub motor_serial: SerialSettings,
+    pub laser: LaserSettings,
+    /// 自动检测时记住的霍尔板设备标识
+    pub hall_usb_id: Option<UsbId>,
+    pub motor_usb_id: Option<UsbId>,
+    pub motor: MotorSettings,
+    /// 上次使用的霍尔串口
+    pub hall_port: Option<String>,
+    pub motor_port: Option<String>,
+    pub laser_addr: Option<String>,
+    pub hall_d: f32,
+    pub laser_d: f32,
+    pub output: OutputSettings,
+    pub pulse_sync: PulseSyncPolicy,
+    pub homing: HomingSettings,
+    /// 当前刀具类型，对应 motion_profiles 中的运动曲线
+    pub cutter_type: String,
+    pub motion_profiles: BTreeMap<String, MotionProfile>,
+    /// 各刀具类型的磨损限值，检测结束时据此给出结论
+    pub wear_limits: BTreeMap<String, WearLimits>,
+    /// 检测时记录设备收发的原始数据，每次检测一个文件
+    pub capture_traffic: bool,
+    /// 控制器固件支持 docs/motor_protocol.md 中的扩展命令（加减速参数、联锁），旧固件保持关闭
+    pub motor_extended_protocol: bool,
+    pub log: LogSettings,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        AppSettings {
+            // 霍尔板一帧数据较长，默认给更宽松的超时
+            hall_serial: SerialSettings {
+                timeout_ms: 2000,
+                ..Default::default()
+            },
+            motor_serial: SerialSettings::default(),
+            laser: LaserSettings::default(),
+            hall_usb_id: None,
+            motor_usb_id: None,
+            motor: MotorSettings::default(),
+            hall_port: None,
+            motor_port: None,
+            laser_addr: None,
+            hall_d: 20.0,
+            laser_d: 428.0,
+            output: OutputSettings::default(),
+            pulse_sync: PulseSyncPolicy::Controller,
+            homing: HomingSettings::default(),
+            cutter_type: DEFAULT_CUTTER_TYPE.into(),
+            motion_profiles: BTreeMap::from([(DEFAULT_CUTTER_TYPE.to_string(), MotionProfile::default())]),
+            wear_limits: BTreeMap::new(),
+            capture_traffic: false,
+            motor_extended_protocol: false,
+            log: LogSettings::default(),
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn validate(&self) -> AppResult<()> {
+        self.hall_serial.validate()?;
+        self.motor_serial.validate()?;
+        self.laser.validate()?;
+        self.motor.validate()?;
+        self.homing.validate()?;
+        self.log.validate()?;
+        for (cutter_type, profile) in &self.motion_profiles {
+            if cutter_type.trim().is_empty() {
+                return Err(AppError::InvalidInput("刀具类型不能为空".into()));
+            }
+            profile.validate()?;
+        }
+        for (cutter_type, limits) in &self.wear_limits {
+            if cutter_type.trim().is_empty() {
+                return Err(AppError::InvalidInput("刀具类型不能为空".into()));
+            }
+            limits.validate()?;
+        }
+        validate_distance("霍尔距离", self.hall_d)?;
+        validate_distance("激光距离", self.laser_d)?;
+        validate_laser_addr(&self.laser_addr)
+    }
+
+    /// 转速超过当前运动曲线的最大转速时降到最大转速，而不是让整份设置失效
+    pub fn normalize(&mut self) {
+        let max_speed = self.motion_profile().max_speed;
+        if self.motor.speed > max_speed {
+            warn!("Motor speed {} exceeds max speed {}, clamped", self.motor.speed, max_speed);
+            self.motor.speed = max_speed;
+        }
+    }
+
+    /// 当前刀具类型的运动曲线，未配置时使用默认曲线
+    pub fn motion_profile(&self) -> MotionProfile {
+        self.motion_profiles.get(&self.cutter_type).cloned().unwrap_or_default()
+    }
+
+    /// 读取设置文件，文件不存在时使用默认值
+    pub fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(text) => AppSettings::from_json(&text),
+            Err(_) => AppSettings::default(),
+        }
+    }
+
+    /// 逐项解析和校验设置，无效的项使用默认值，运动曲线和磨损限值只丢弃无效的刀具类型
+    pub fn from_json(text: &str) -> Self {
+        let mut settings = AppSettings::default();
+        let mut obj = match serde_json::from_str::<Value>(text) {
+            Ok(Value::Object(obj)) => obj,
+            Ok(_) => {
+                warn!("Settings file is not an object, using defaults");
+                return settings;
+            }
+            Err(e) => {
+                warn!("Failed to parse settings: {}", e);
+                return settings;
+            }
+        };
+        macro_rules! load_field {
+            ($field:ident, $check:expr) => {
+                if let Some(v) = section(&mut obj, stringify!($field), $check) {
+                    settings.$field = v;
+                }
+            };
+        }
+        load_field!(hall_serial, SerialSettings::validate);
+        load_field!(motor_serial, SerialSettings::validate);
+        load_field!(laser, LaserSettings::validate);
+        load_field!(hall_usb_id, |_| Ok(()));
+        load_field!(motor_usb_id, |_| Ok(()));
+        load_field!(motor, MotorSettings::validate);
+        load_field!(hall_port, |_| Ok(()));
+        load_field!(motor_port, |_| Ok(()));
+        load_field!(laser_addr, validate_laser_addr);
+        load_field!(hall_d, |d| validate_distance("霍尔距离", *d));
+        load_field!(laser_d, |d| validate_distance("激光距离", *d));
+        load_field!(output, |_| Ok(()));
+        load_field!(pulse_sync, |_| Ok(()));
+        load_field!(homing, HomingSettings::validate);
+        load_field!(cutter_type, |_| Ok(()));
+        load_field!(capture_traffic, |_| Ok(()));
+        load_field!(motor_extended_protocol, |_| Ok(()));
+        load_field!(log, LogSettings::validate);
+        if let Some(profiles) = entries(&mut obj, "motion_profiles", MotionProfile::validate) {
+            settings.motion_profiles = profiles;
+        }
+        if let Some(limits) = entries(&mut obj, "wear_limits", WearLimits::validate) {
+            settings.wear_limits = limits;
+        }
+        settings.normalize();
+        settings
+    }
+
+    pub fn save(&self, path: &Path) -> AppResult<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| AppError::io(Device::File, e))?;
+        }
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|e| AppError::parse(Device::File, e.to_string()))?;
+        std::fs::write(path, text).map_err(|e| AppError::io(Device::File, e))
+    }
+}
+
+fn validate_distance(name: &str, d: f32) -> AppResult<()> {
+    if !d.is_finite() || d < 0.0 {
+        return Err(AppError::InvalidInput(format!("{}{}不合法", name, d)));
+    }
+    Ok(())
+}
+
+fn validate_laser_addr(addr: &Option<String>) -> AppResult<()> {
+    if matches!(addr, Some(a) if a.trim().is_empty()) {
+        return Err(AppError::InvalidInput("激光地址不能为空".into()));
+    }
+    Ok(())
+}
+
+/// 取出设置中的一项，缺失时返回空，无法解析或校验失败时记录警告后返回空
+fn section<T: DeserializeOwned>(
+    obj: &mut Map<String, Value>,
+    key: &str,
+    check: impl Fn(&T) -> AppResult<()>,
+) -> Option<T> {
+    let value = obj.remove(key)?;
+    let parsed = serde_json::from_value::<T>(value).map_err(|e| AppError::parse(Device::File, e.to_string()));
+    match parsed.and_then(|v| check(&v).map(|_| v)) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            warn!("Invalid setting {}, using default: {}", key, e);
+            None
+        }
+    }
+}
+
+/// 取出按刀具类型保存的设置，逐个校验，只丢弃无效的条目
+fn entries<T: DeserializeOwned>(
+    obj: &mut Map<String, Value>,
+    key: &str,
+    check: impl Fn(&T) -> AppResult<()>,
+) -> Option<BTreeMap<String, T>> {
+    let map = section::<Map<String, Value>>(obj, key, |_| Ok(()))?;
+    let mut result = BTreeMap::new();
+    for (cutter_type, value) in map {
+        let parsed = serde_json::from_value::<T>(value).map_err(|e| AppError::parse(Device::File, e.to_string()));
+        let checked = parsed.and_then(|v| {
+            if cutter_type.trim().is_empty() {
+                return Err(AppError::InvalidInput("刀具类型不能为空".into()));
+            }
+            check(&v).map(|_| v)
+        });
+        match checked {
+            Ok(v) => {
+                result.insert(cutter_type, v);
+            }
+            Err(e) => warn!("Invalid {} for {:?}, dropped: {}", key, cutter_type, e),
+        }
+    }
+    Some(result)
+}
+
+pub fn settings_path(app: &AppHandle) -> AppResult<PathBuf> {
+    let mut path = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| AppError::io(Device::File, e))?;
+    path.push(SETTINGS_FILE);
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(serial_number: Option<&str>) -> UsbPortInfo {
+        UsbPortInfo {
+            vid: 0x1a86,
+            pid: 0x7523,
+            serial_number: serial_number.map(str::to_string),
+            manufacturer: None,
+            product: None,
+            interface: None,
+        }
+    }
+
+    #[test]
+    fn load_keeps_valid_sections() {
+        let text = r#"{
+            "hall_port": "COM3",
+            "hall_d": -1,
+            "motor": {"step_pulse": 80, "single_circle_pulse": 0, "speed": 1.0},
+            "laser_d": 300,
+            "unknown": true
+        }"#;
+        let settings = AppSettings::from_json(text);
+        assert_eq!(settings.hall_port.as_deref(), Some("COM3"));
+        assert_eq!(settings.laser_d, 300.0);
+        // 无效的项使用默认值
+        assert_eq!(settings.hall_d, AppSettings::default().hall_d);
+        assert_eq!(settings.motor, MotorSettings::default());
+    }
+
+    #[test]
+    fn load_drops_only_invalid_entries() {
+        let text = r#"{
+            "motion_profiles": {
+                "A": {"max_speed": 2.0},
+                "B": {"max_speed": -1.0},
+                " ": {}
+            }
+        }"#;
+        let settings = AppSettings::from_json(text);
+        let keys: Vec<&String> = settings.motion_profiles.keys().collect();
+        assert_eq!(keys, vec!["A"]);
+        assert_eq!(settings.motion_profiles["A"].max_speed, 2.0);
+    }
+
+    #[test]
+    fn load_clamps_speed_to_profile() {
+        let text = r#"{
+            "motor": {"step_pulse": 40, "single_circle_pulse": 15000, "speed": 30.0},
+            "motion_profiles": {"默认": {"max_speed": 10.0}},
+            "hall_port": "COM3"
+        }"#;
+        let settings = AppSettings::from_json(text);
+        assert_eq!(settings.motor.speed, 10.0);
+        assert_eq!(settings.hall_port.as_deref(), Some("COM3"));
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn load_falls_back_on_unparsable_file() {
+        assert_eq!(AppSettings::from_json("not json"), AppSettings::default());
+        assert_eq!(AppSettings::from_json("[]"), AppSettings::default());
+    }
+
+    #[test]
+    fn usb_id_matches_serial_number() {
+        let id = UsbId::of("COM3", &info(Some("A1")));
+        assert_eq!(id.port, None);
+        assert!(id.matches("COM7", &info(Some("A1"))));
+        assert!(!id.matches("COM3", &info(Some("B2"))));
+    }
+
+    #[test]
+    fn usb_id_without_serial_matches_port() {
+        let id = UsbId::of("COM3", &info(None));
+        assert!(id.matches("COM3", &info(None)));
+        assert!(!id.matches("COM4", &info(None)));
+    }
+
+    #[test]
+    fn legacy_usb_id_matches_nothing() {
+        let id: UsbId = serde_json::from_str(r#"{"vid":6790,"pid":29987}"#).unwrap();
+        assert!(!id.matches("COM3", &info(None)));
+    }
+}
@@ -1,15 +1,27 @@
+use crate::error::{AppError, AppResult};
+use crate::serial::HallStat;
+use crate::verdict::Verdict;
 use crate::{AppWrapper};
+use log::{debug, error, trace, warn};
+use std::sync::Arc;
 use chrono::Local;
 use rusqlite::{params, Connection};
 use tauri::path::BaseDirectory;
 use tauri::Manager;
 use umya_spreadsheet;
 
+pub const PROJECT_RUNNING: &str = "running";
+pub const PROJECT_FINISHED: &str = "finished";
+pub const PROJECT_ABORTED: &str = "aborted";
+/// 项目来源，实时采集或回放已保存的数据
+pub const PROJECT_SCAN: &str = "scan";
+pub const PROJECT_REPLAY: &str = "replay";
+
 #[derive(Clone, serde::Serialize)]
 pub struct Data {
     id: i32,
     parent_id: i32,
-    angle: i32,
+    angle: f32,
     data1: i32,
     data2: i32,
     data3: i32,
@@ -21,42 +33,155 @@ pub struct Data {
     data9: i32,
 }
 
-pub fn check_project_table_is_exit() -> Result<Connection, String> {
-    let conn = Connection::open("sqlite.db").expect("Can't open sqlite.db");
+#[derive(Clone, serde::Serialize)]
+pub struct DataStat {
+    angle: f32,
+    channel: i32,
+    samples: i32,
+    mean: f32,
+    std: f32,
+    min: i32,
+    max: i32,
+    rejected: bool,
+}
+
+/// 创建数据表并补充旧版本数据库缺少的列，只在启动时执行一次
+pub fn init_db() -> AppResult<()> {
+    let conn = connect_to_db()?;
     match conn.execute(
         "CREATE TABLE IF NOT EXISTS project (\
     id INTEGER PRIMARY KEY AUTOINCREMENT,
     name TEXT NOT NULL,
     hall_d REAL NOT NULL,
     laser_d REAL NOT NULL,
-    time DATETIME)",
+    time DATETIME,
+    status TEXT)",
         [],
     ) {
-        Ok(_) => Ok(conn),
-        Err(_) => Err(String::from("Can't create project")),
+        Ok(_) => {}
+        Err(e) => return Err(AppError::Database(format!("无法创建项目表: {}", e))),
     }
+    // 旧版本数据库没有项目状态列
+    add_column_if_missing(&conn, "project", "status", "TEXT")?;
+    // 检测结论，verdict 为 pass / fail / undetermined，verdict_detail 为结论的 JSON
+    add_column_if_missing(&conn, "project", "verdict", "TEXT")?;
+    add_column_if_missing(&conn, "project", "verdict_detail", "TEXT")?;
+    // 项目来源，旧版本数据库没有该列，视为实时采集
+    add_column_if_missing(&conn, "project", "kind", "TEXT")?;
+    create_data_tables(&conn)
 }
 
-pub fn create_project(name: String, hall_d: f32, laser_d: f32) -> Result<i64, String> {
-    let conn = check_project_table_is_exit().expect("Can't create project table");
+/// 新建项目，kind 为 PROJECT_SCAN / PROJECT_REPLAY
+pub fn create_project(name: String, kind: &str, hall_d: f32, laser_d: f32) -> AppResult<i64> {
+    let conn = connect_to_db()?;
     match conn.execute(
-        "INSERT INTO project (name,time,hall_d,laser_d) VALUES (?,?,?,?)",
+        "INSERT INTO project (name,time,hall_d,laser_d,status,kind) VALUES (?,?,?,?,?,?)",
         // 将 `data` 中的值绑定到 SQL 语句中的占位符
-        params![name, Local::now().timestamp(),hall_d,laser_d],
+        params![name, Local::now().timestamp(),hall_d,laser_d,PROJECT_RUNNING,kind],
     ) {
         Ok(_) => {
-            println!("Data inserted successfully");
+            debug!("Data inserted successfully");
             Ok(conn.last_insert_rowid()) // 插入成功，返回 Ok
         }
         Err(e) => {
-            eprintln!("Error inserting data: {}", e);
-            Err(e.to_string()) // 如果插入失败，返回错误信息
+            error!("Error inserting data: {}", e);
+            Err(e.into()) // 如果插入失败，返回错误信息
         }
     }
 }
 
-pub fn connect_to_db() -> Result<Connection, String> {
-    let conn = Connection::open("sqlite.db").expect("Can't open sqlite.db");
+/// 更新项目状态，取值为 PROJECT_RUNNING / PROJECT_FINISHED / PROJECT_ABORTED
+pub fn set_project_status(id: i64, status: &str) -> AppResult<()> {
+    let conn = connect_to_db()?;
+    conn.execute("UPDATE project SET status = ? WHERE id = ?", params![status, id])?;
+    Ok(())
+}
+
+pub fn set_project_verdict(id: i64, verdict: &Verdict) -> AppResult<()> {
+    let conn = connect_to_db()?;
+    let detail = serde_json::to_string(verdict).map_err(|e| AppError::Database(e.to_string()))?;
+    conn.execute(
+        "UPDATE project SET verdict = ?, verdict_detail = ? WHERE id = ?",
+        params![verdict.result.as_str(), detail, id],
+    )?;
+    Ok(())
+}
+
+/// 项目的检测结论，未判定时为空
+#[tauri::command]
+pub fn get_project_verdict(parent_id: i64) -> AppResult<Option<Verdict>> {
+    let conn = connect_to_db()?;
+    let detail: Option<String> = conn
+        .query_row("SELECT verdict_detail FROM project WHERE id = ?", [parent_id], |row| row.get(0))
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::InvalidInput(format!("项目{}不存在", parent_id)),
+            e => e.into(),
+        })?;
+    match detail {
+        Some(d) => serde_json::from_str(&d)
+            .map(Some)
+            .map_err(|e| AppError::Database(format!("检测结论格式错误: {}", e))),
+        None => Ok(None),
+    }
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct Project {
+    pub id: i64,
+    pub name: String,
+    pub hall_d: f32,
+    pub laser_d: f32,
+    pub status: Option<String>,
+    /// 项目来源，旧版本创建的项目为空
+    pub kind: Option<String>,
+}
+
+impl Project {
+    pub fn is_replay(&self) -> bool {
+        self.kind.as_deref() == Some(PROJECT_REPLAY)
+    }
+}
+
+pub fn get_project(id: i64) -> AppResult<Project> {
+    let conn = connect_to_db()?;
+    conn.query_row(
+        "SELECT id, name, hall_d, laser_d, status, kind FROM project WHERE id = ?",
+        [id],
+        |row| {
+            Ok(Project {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                hall_d: row.get(2)?,
+                laser_d: row.get(3)?,
+                status: row.get(4)?,
+                kind: row.get(5)?,
+            })
+        },
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::InvalidInput(format!("项目{}不存在", id)),
+        e => e.into(),
+    })
+}
+
+/// 为已存在的表补充新增的列，兼容旧版本创建的数据库
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> AppResult<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .any(|name| matches!(name, Ok(n) if n == column));
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl), [])?;
+    }
+    Ok(())
+}
+
+/// 打开数据库，表结构由 init_db 在启动时创建
+pub fn connect_to_db() -> AppResult<Connection> {
+    Ok(Connection::open("sqlite.db")?)
+}
+
+fn create_data_tables(conn: &Connection) -> AppResult<()> {
     match conn.execute(
         "CREATE TABLE IF NOT EXISTS data (\
     id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -70,50 +195,113 @@ pub fn connect_to_db() -> Result<Connection, String> {
     data6 INTEGER,\
     data7 INTEGER,\
     data8 INTEGER,\
-    data9 INTEGER)",
+    data9 INTEGER,\
+    time INTEGER)",
+        [],
+    ) {
+        Ok(_) => {}
+        Err(e) => return Err(AppError::Database(format!("无法创建数据表: {}", e))),
+    }
+    // 旧版本数据库没有采样时间列
+    add_column_if_missing(conn, "data", "time", "INTEGER")?;
+    // 每个角度每个通道一行，记录多次采样的统计结果
+    match conn.execute(
+        "CREATE TABLE IF NOT EXISTS data_stat (\
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    parent_id INTEGER NOT NULL,
+    angle REAL NOT NULL,
+    channel INTEGER NOT NULL,
+    samples INTEGER NOT NULL,
+    mean REAL,\
+    std REAL,\
+    min INTEGER,\
+    max INTEGER,\
+    rejected INTEGER NOT NULL DEFAULT 0)",
         [],
     ) {
-        Ok(_) => Ok(conn),
-        Err(_) => Err(String::from("Can't open sqlite.db")),
+        Ok(_) => Ok(()),
+        Err(e) => Err(AppError::Database(format!("无法创建统计表: {}", e))),
     }
 }
 
-pub fn insert_data(parent_id: i64, angle: f32, data: &Vec<i32>) -> Result<(), String> {
-    let conn = connect_to_db().expect("Failed to connect to DB");
+/// 采集过程中每个角度都会写入，conn 由调用方在整个检测期间复用
+pub fn insert_data(conn: &Connection, parent_id: i64, angle: f32, time: i64, data: &Vec<i32>) -> AppResult<()> {
     if data.len() != 9 {
-        return Err("Data must contain exactly 9 elements".to_string());
+        return Err(AppError::InvalidInput("霍尔数据必须为9个通道".into()));
     }
 
-    // 使用 `conn.execute` 执行 INSERT INTO 语句
-    match conn.execute(
-        "INSERT INTO data (parent_id, angle, data1, data2, data3, data4, data5, data6, data7, data8, data9) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO data (parent_id, angle, data1, data2, data3, data4, data5, data6, data7, data8, data9, time) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )?;
+    match stmt.execute(
         // 将 `data` 中的值绑定到 SQL 语句中的占位符
         params![
             parent_id,  angle,
             data[0], data[1], data[2], data[3], data[4],
-            data[5], data[6], data[7], data[8]
+            data[5], data[6], data[7], data[8], time
         ],
     ) {
         Ok(_) => {
-            println!("Data inserted successfully");
+            trace!("Data inserted successfully");
             Ok(()) // 插入成功，返回 Ok
         }
         Err(e) => {
-            eprintln!("Error inserting data: {}", e);
-            Err(e.to_string()) // 如果插入失败，返回错误信息
+            error!("Error inserting data: {}", e);
+            Err(e.into()) // 如果插入失败，返回错误信息
         }
     }
 }
 
+pub fn insert_data_stat(
+    conn: &mut Connection,
+    parent_id: i64,
+    angle: f32,
+    stat: &HallStat,
+    rejected: bool,
+) -> AppResult<()> {
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare_cached(
+            "INSERT INTO data_stat (parent_id, angle, channel, samples, mean, std, min, max, rejected) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )?;
+        for ch in 0..stat.mean.len() {
+            stmt.execute(params![
+                parent_id, angle, ch as i32 + 1, stat.samples,
+                stat.mean[ch], stat.std[ch], stat.min[ch], stat.max[ch], rejected
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// 按写入顺序读取项目的霍尔数据，返回角度、采样时间和各通道读数
+pub fn get_hall_rows(parent_id: i64) -> AppResult<Vec<(f32, Option<i64>, Vec<i32>)>> {
+    let conn = connect_to_db()?;
+    let mut stmt = conn.prepare(
+        "SELECT angle, time, data1, data2, data3, data4, data5, data6, data7, data8, data9 FROM data WHERE parent_id = ? ORDER BY id",
+    )?;
+    let rows = stmt.query_map([parent_id], |row| {
+        let mut data = Vec::with_capacity(9);
+        for i in 2..11 {
+            data.push(row.get::<_, i32>(i)?);
+        }
+        Ok((row.get(0)?, row.get(1)?, data))
+    })?;
+    let mut list = Vec::new();
+    for row in rows {
+        list.push(row?);
+    }
+    Ok(list)
+}
+
 #[tauri::command]
 pub fn get_data_by_parent_id(
-    _state: tauri::State<AppWrapper>,
     parent_id: i32,
-) -> Result<Vec<Data>, String> {
-    let conn = connect_to_db().expect("Failed to connect to DB");
+) -> AppResult<Vec<Data>> {
+    let conn = connect_to_db()?;
     let mut stmt = conn
-        .prepare("SELECT * FROM data WHERE WHERE parent_id = ?")
-        .map_err(|e| e.to_string())?;
+        .prepare("SELECT id, parent_id, angle, data1, data2, data3, data4, data5, data6, data7, data8, data9 FROM data WHERE parent_id = ?")?;
     let rows = stmt
         .query_map([parent_id], |row| {
             Ok(Data {
@@ -130,23 +318,53 @@ pub fn get_data_by_parent_id(
                 data8: row.get(10)?,
                 data9: row.get(11)?,
             })
-        })
-        .map_err(|e| e.to_string())?;
+        })?;
     let mut data_list = Vec::new();
     for row in rows {
         match row {
             Ok(data) => data_list.push(data),
-            Err(e) => eprintln!("Error fetching row: {}", e),
+            Err(e) => warn!("Error fetching row: {}", e),
         }
     }
 
     Ok(data_list)
 }
 #[tauri::command]
-pub fn gen_xlsx(state: tauri::State<AppWrapper>, parent_id: i32) -> Result<String, String> {
+pub fn get_stat_by_parent_id(parent_id: i32) -> AppResult<Vec<DataStat>> {
+    let conn = connect_to_db()?;
+    let mut stmt = conn
+        .prepare("SELECT angle, channel, samples, mean, std, min, max, rejected FROM data_stat WHERE parent_id = ? ORDER BY id")?;
+    let rows = stmt
+        .query_map([parent_id], |row| {
+            Ok(DataStat {
+                angle: row.get(0)?,
+                channel: row.get(1)?,
+                samples: row.get(2)?,
+                mean: row.get(3)?,
+                std: row.get(4)?,
+                min: row.get(5)?,
+                max: row.get(6)?,
+                rejected: row.get(7)?,
+            })
+        })?;
+    let mut stat_list = Vec::new();
+    for row in rows {
+        match row {
+            Ok(stat) => stat_list.push(stat),
+            Err(e) => warn!("Error fetching row: {}", e),
+        }
+    }
+
+    Ok(stat_list)
+}
+#[tauri::command]
+pub fn gen_xlsx(state: tauri::State<'_, Arc<AppWrapper>>, parent_id: i32) -> AppResult<String> {
+    let project = get_project(parent_id as i64)?;
     let mut book = umya_spreadsheet::new_file();
-    let conn = connect_to_db().expect("Failed to connect to DB");
-    let sheet = book.get_sheet_by_name_mut("Sheet1").unwrap();
+    let conn = connect_to_db()?;
+    let sheet = book
+        .get_sheet_by_name_mut("Sheet1")
+        .ok_or_else(|| AppError::Export("无法创建工作表".into()))?;
     sheet.get_cell_mut("A1").set_value("角度");
     sheet.get_cell_mut("B1").set_value("数据1");
     sheet.get_cell_mut("C1").set_value("数据2");
@@ -157,14 +375,18 @@ pub fn gen_xlsx(state: tauri::State<AppWrapper>, parent_id: i32) -> Result<Strin
     sheet.get_cell_mut("H1").set_value("数据7");
     sheet.get_cell_mut("I1").set_value("数据8");
     sheet.get_cell_mut("J1").set_value("数据9");
+    // 回放项目的数据来自已保存的数据，导出时注明来源
+    sheet.get_cell_mut("L1").set_value("数据来源");
+    sheet
+        .get_cell_mut("L2")
+        .set_value(if project.is_replay() { "回放" } else { "实时采集" });
 
-    let mut stmt = conn.prepare("SELECT angle, data1, data2, data3, data4, data5, data6, data7, data8, data9 FROM data WHERE parent_id = ?")
-        .map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare("SELECT angle, data1, data2, data3, data4, data5, data6, data7, data8, data9 FROM data WHERE parent_id = ?")?;
 
     let rows = stmt
         .query_map([parent_id], |row| {
             Ok((
-                row.get::<_, i32>(0)?, // 直接获取数据库中的时间字符串
+                row.get::<_, f32>(0)?, // 角度
                 row.get::<_, i32>(1)?, // 获取数据列
                 row.get::<_, i32>(2)?,
                 row.get::<_, i32>(3)?,
@@ -175,13 +397,13 @@ pub fn gen_xlsx(state: tauri::State<AppWrapper>, parent_id: i32) -> Result<Strin
                 row.get::<_, i32>(8)?,
                 row.get::<_, i32>(9)?,
             ))
-        })
-        .map_err(|e| e.to_string())?;
-    let mut row_index = 0;
-    for (i, row) in rows.enumerate() {
+        })?;
+    let mut count = 0;
+    for row in rows {
         match row {
             Ok(d) => {
-                row_index = i + 2;
+                count += 1;
+                let row_index = count + 1;
                 sheet
                     .get_cell_mut(format!("A{}", row_index.to_string()).as_str())
                     .set_value(&d.0.to_string());
@@ -213,18 +435,44 @@ pub fn gen_xlsx(state: tauri::State<AppWrapper>, parent_id: i32) -> Result<Strin
                     .get_cell_mut(format!("J{}", row_index.to_string()).as_str())
                     .set_value(&d.9.to_string());
             }
-            Err(_) => {}
+            Err(e) => warn!("Error fetching row: {}", e),
+        }
+    }
+    if let Some(verdict) = get_project_verdict(parent_id as i64)? {
+        let sheet = book
+            .new_sheet("检测结论")
+            .map_err(|e| AppError::Export(e.to_string()))?;
+        let m = &verdict.metrics;
+        let l = &verdict.limits;
+        let fmt = |v: Option<f32>| v.map(|v| format!("{:.3}", v)).unwrap_or_else(|| "-".into());
+        let rows = [
+            ("刀具类型", verdict.cutter_type.clone(), String::new()),
+            ("结论", verdict.label().to_string(), String::new()),
+            ("说明", verdict.summary(), String::new()),
+            ("名义半径", String::new(), fmt(l.nominal_radius)),
+            ("径向磨损", fmt(m.radial_wear), fmt(l.max_radial_wear)),
+            ("径向跳动", fmt(m.radial_runout), fmt(l.max_radial_runout)),
+            ("刃口平台宽度", fmt(m.edge_flattening), fmt(l.max_edge_flattening)),
+            ("霍尔偏差", fmt(m.hall_deviation), fmt(l.max_hall_deviation)),
+        ];
+        sheet.get_cell_mut("A1").set_value("项目");
+        sheet.get_cell_mut("B1").set_value("测量值");
+        sheet.get_cell_mut("C1").set_value("限值");
+        for (i, (name, value, limit)) in rows.iter().enumerate() {
+            sheet.get_cell_mut(format!("A{}", i + 2).as_str()).set_value(*name);
+            sheet.get_cell_mut(format!("B{}", i + 2).as_str()).set_value(value);
+            sheet.get_cell_mut(format!("C{}", i + 2).as_str()).set_value(limit);
         }
     }
     let app_handle = &state.app_handler;
     let mut path = app_handle
         .path()
         .resolve("", BaseDirectory::AppCache)
-        .unwrap();
+        .map_err(|e| AppError::Export(e.to_string()))?;
     path.push("table.xlsx");
     let r = umya_spreadsheet::writer::xlsx::write(&book, path);
     match r {
-        Ok(_) => Ok(format!("导出成功，共导出{}条数据!", row_index - 2)),
-        Err(e) => Err(format!("导出失败: {}", e)),
+        Ok(_) => Ok(format!("导出成功，共导出{}条数据!", count)),
+        Err(e) => Err(AppError::Export(e.to_string())),
     }
 }
@@ -0,0 +1,279 @@
+use crate::serial::LaserData;
+use crate::settings::WearLimits;
+use serde::{Deserialize, Serialize};
+
+/// 距轮廓最大半径在此范围内的点视为刃口平台，单位 mm
+const FLAT_BAND: f32 = 0.05;
+
+/// 一次检测得到的磨损指标，数据不足时为空
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct WearMetrics {
+    /// 名义半径减去各角度中最小的刃口半径，即最大径向磨损量，未设置名义半径时为空
+    pub radial_wear: Option<f32>,
+    /// 各角度刃口半径的极差，即径向跳动（偏心），均匀磨损时为 0
+    pub radial_runout: Option<f32>,
+    /// 各角度中刃口平台在 z 方向的最大宽度
+    pub edge_flattening: Option<f32>,
+    /// 霍尔电压相对各通道整圈均值的最大偏差
+    pub hall_deviation: Option<f32>,
+}
+
+/// 采集过程中累计计算磨损指标所需的数据
+#[derive(Default)]
+pub struct WearAccumulator {
+    peaks: Vec<f32>,
+    flattening: Option<f32>,
+    volts: Vec<Vec<f32>>,
+}
+
+impl WearAccumulator {
+    pub fn add_laser(&mut self, points: &[LaserData]) {
+        let radius: Vec<f32> = points.iter().map(|p| p.x.hypot(p.y)).collect();
+        let Some(peak) = radius.iter().cloned().reduce(f32::max) else { return };
+        self.peaks.push(peak);
+        let flat = points.iter().zip(&radius).filter(|(_, r)| **r >= peak - FLAT_BAND).map(|(p, _)| p.z);
+        let (min, max) = flat.fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), z| (lo.min(z), hi.max(z)));
+        let width = max - min;
+        self.flattening = Some(self.flattening.map_or(width, |w| w.max(width)));
+    }
+
+    pub fn add_hall(&mut self, volts: &[f32]) {
+        self.volts.push(volts.to_vec());
+    }
+
+    /// nominal_radius 为新刀的刃口半径，用于计算径向磨损量
+    pub fn metrics(&self, nominal_radius: Option<f32>) -> WearMetrics {
+        let min = self.peaks.iter().cloned().reduce(f32::min);
+        let max = self.peaks.iter().cloned().reduce(f32::max);
+        let radial_runout = match (min, max) {
+            (Some(min), Some(max)) if self.peaks.len() >= 2 => Some(max - min),
+            _ => None,
+        };
+        WearMetrics {
+            radial_wear: nominal_radius.zip(min).map(|(nominal, min)| nominal - min),
+            radial_runout,
+            edge_flattening: self.flattening,
+            hall_deviation: self.hall_deviation(),
+        }
+    }
+
+    fn hall_deviation(&self) -> Option<f32> {
+        let channels = self.volts.first()?.len();
+        let mut deviation: Option<f32> = None;
+        for ch in 0..channels {
+            let values: Vec<f32> = self.volts.iter().filter_map(|v| v.get(ch).copied()).collect();
+            let mean = values.iter().sum::<f32>() / values.len() as f32;
+            for v in values {
+                let d = (v - mean).abs();
+                deviation = Some(deviation.map_or(d, |m| m.max(d)));
+            }
+        }
+        deviation
+    }
+}
+
+/// 判定结果，有项目超限时为不合格，否则有项目缺少数据时为未判定
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerdictResult {
+    Pass,
+    Fail,
+    Undetermined,
+}
+
+impl VerdictResult {
+    /// 保存到数据库的取值
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VerdictResult::Pass => "pass",
+            VerdictResult::Fail => "fail",
+            VerdictResult::Undetermined => "undetermined",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            VerdictResult::Pass => "合格",
+            VerdictResult::Fail => "不合格",
+            VerdictResult::Undetermined => "未判定",
+        }
+    }
+}
+
+/// 检测结论
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Verdict {
+    pub result: VerdictResult,
+    pub cutter_type: String,
+    pub metrics: WearMetrics,
+    pub limits: WearLimits,
+    /// 超出限值的项目
+    pub failures: Vec<String>,
+    /// 设置了限值但缺少数据无法判定的项目
+    pub unevaluated: Vec<String>,
+}
+
+impl Verdict {
+    pub fn evaluate(cutter_type: String, metrics: WearMetrics, limits: WearLimits) -> Self {
+        let mut failures = Vec::new();
+        let mut unevaluated = Vec::new();
+        for (name, value, limit) in [
+            ("径向磨损", metrics.radial_wear, limits.max_radial_wear),
+            ("径向跳动", metrics.radial_runout, limits.max_radial_runout),
+            ("刃口平台宽度", metrics.edge_flattening, limits.max_edge_flattening),
+            ("霍尔偏差", metrics.hall_deviation, limits.max_hall_deviation),
+        ] {
+            match (value, limit) {
+                (Some(v), Some(l)) if v > l => failures.push(format!("{}{:.3}超出限值{:.3}", name, v, l)),
+                (None, Some(_)) => unevaluated.push(name.to_string()),
+                _ => {}
+            }
+        }
+        // 缺少数据不能算合格
+        let result = if !failures.is_empty() {
+            VerdictResult::Fail
+        } else if !unevaluated.is_empty() {
+            VerdictResult::Undetermined
+        } else {
+            VerdictResult::Pass
+        };
+        Verdict {
+            result,
+            cutter_type,
+            metrics,
+            limits,
+            failures,
+            unevaluated,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        self.result.label()
+    }
+
+    /// 供消息和导出显示的结论说明
+    pub fn summary(&self) -> String {
+        let mut text = format!("刀具类型{}：{}", self.cutter_type, self.label());
+        if !self.failures.is_empty() {
+            text.push_str(&format!("，{}", self.failures.join("，")));
+        }
+        if !self.unevaluated.is_empty() {
+            text.push_str(&format!("；缺少数据未判定：{}", self.unevaluated.join("、")));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(wear: Option<f32>, flat: Option<f32>, hall: Option<f32>) -> WearMetrics {
+        WearMetrics {
+            radial_wear: wear,
+            radial_runout: wear.map(|_| 0.0),
+            edge_flattening: flat,
+            hall_deviation: hall,
+        }
+    }
+
+    fn limits(wear: Option<f32>, flat: Option<f32>, hall: Option<f32>) -> WearLimits {
+        WearLimits {
+            nominal_radius: wear.map(|_| 10.0),
+            max_radial_wear: wear,
+            max_radial_runout: None,
+            max_edge_flattening: flat,
+            max_hall_deviation: hall,
+        }
+    }
+
+    fn evaluate(metrics: WearMetrics, limits: WearLimits) -> Verdict {
+        Verdict::evaluate("默认".into(), metrics, limits)
+    }
+
+    #[test]
+    fn within_limits_passes() {
+        let v = evaluate(metrics(Some(0.01), Some(0.1), None), limits(Some(0.02), Some(0.2), None));
+        assert_eq!(v.result, VerdictResult::Pass);
+        assert!(v.failures.is_empty() && v.unevaluated.is_empty());
+    }
+
+    #[test]
+    fn missing_data_is_undetermined() {
+        // 只有霍尔数据时激光相关的限值无法判定
+        let v = evaluate(metrics(None, None, Some(0.1)), limits(Some(0.02), None, Some(0.5)));
+        assert_eq!(v.result, VerdictResult::Undetermined);
+        assert_eq!(v.unevaluated, vec!["径向磨损".to_string()]);
+        assert_eq!(v.label(), "未判定");
+        // 没有设置限值的项目缺少数据不影响结论
+        let v = evaluate(metrics(None, None, Some(0.1)), limits(None, None, Some(0.5)));
+        assert_eq!(v.result, VerdictResult::Pass);
+    }
+
+    #[test]
+    fn failure_takes_precedence() {
+        let v = evaluate(metrics(Some(0.05), None, None), limits(Some(0.02), Some(0.2), None));
+        assert_eq!(v.result, VerdictResult::Fail);
+        assert_eq!(v.failures.len(), 1);
+        assert_eq!(v.unevaluated, vec!["刃口平台宽度".to_string()]);
+        assert!(v.summary().contains("不合格"));
+        assert_eq!(v.result.as_str(), "fail");
+    }
+
+    fn profile(radius: f32, z: &[f32]) -> Vec<LaserData> {
+        z.iter().map(|&z| LaserData { x: radius, y: 0.0, z }).collect()
+    }
+
+    #[test]
+    fn accumulator_measures_wear_against_nominal_radius() {
+        let mut acc = WearAccumulator::default();
+        assert!(acc.metrics(Some(10.0)).radial_wear.is_none());
+        assert!(acc.metrics(Some(10.0)).hall_deviation.is_none());
+        acc.add_laser(&profile(9.8, &[0.0]));
+        // 跳动至少需要两个轮廓
+        assert!(acc.metrics(Some(10.0)).radial_runout.is_none());
+        acc.add_laser(&profile(9.8, &[0.0]));
+        acc.add_laser(&[]);
+        // 均匀磨损时没有跳动，但相对名义半径有磨损
+        let m = acc.metrics(Some(10.0));
+        assert!((m.radial_wear.unwrap() - 0.2).abs() < 1e-5);
+        assert!(m.radial_runout.unwrap().abs() < 1e-5);
+        assert!(acc.metrics(None).radial_wear.is_none());
+        acc.add_laser(&profile(9.5, &[0.0]));
+        let m = acc.metrics(Some(10.0));
+        assert!((m.radial_wear.unwrap() - 0.5).abs() < 1e-5);
+        assert!((m.radial_runout.unwrap() - 0.3).abs() < 1e-5);
+    }
+
+    #[test]
+    fn uniform_wear_fails_wear_limit_but_passes_runout() {
+        let mut acc = WearAccumulator::default();
+        acc.add_laser(&profile(9.7, &[0.0]));
+        acc.add_laser(&profile(9.7, &[0.0]));
+        let limits = WearLimits {
+            nominal_radius: Some(10.0),
+            max_radial_wear: Some(0.2),
+            max_radial_runout: Some(0.05),
+            ..Default::default()
+        };
+        let v = evaluate(acc.metrics(limits.nominal_radius), limits);
+        assert_eq!(v.result, VerdictResult::Fail);
+        assert_eq!(v.failures.len(), 1);
+        assert!(v.failures[0].starts_with("径向磨损"));
+    }
+
+    #[test]
+    fn accumulator_measures_flat_width_and_hall_deviation() {
+        let mut acc = WearAccumulator::default();
+        // 距最大半径 0.05mm 以内的点构成平台，其 z 方向跨度为平台宽度
+        let mut points = profile(10.0, &[1.0, 1.3]);
+        points.push(LaserData { x: 9.0, y: 0.0, z: 5.0 });
+        acc.add_laser(&points);
+        acc.add_laser(&profile(10.0, &[0.0, 0.1]));
+        acc.add_hall(&[1.0, 2.0]);
+        acc.add_hall(&[3.0, 2.0]);
+        let m = acc.metrics(None);
+        assert!((m.edge_flattening.unwrap() - 0.3).abs() < 1e-5);
+        assert!((m.hall_deviation.unwrap() - 1.0).abs() < 1e-5);
+    }
+}
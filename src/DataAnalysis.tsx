@@ -177,7 +177,7 @@ class DataAnalysis extends Component {
                             }).catch(err => {
                                 NotificationPlugin.error({
                                     title: '数据导出失败',
-                                    content: err,
+                                    content: err?.message ?? String(err),
                                     placement: 'top-right',
                                     duration: 3000,
                                     offset: [0, 0],
@@ -19,6 +19,14 @@ import {FileIcon} from 'tdesign-icons-react';
 interface port_info {
     port: string;
     info: string;
+    port_type: string;
+    vid?: number;
+    pid?: number;
+    serial_number?: string;
+    manufacturer?: string;
+    product?: string;
+    interface?: number;
+    device?: string;
 }
 
 interface hall_data {
@@ -30,6 +38,14 @@ interface serial_list {
     port_vec: port_info[];
 }
 
+// 扫描仪不提供型号和序列号，发现结果只有应答的地址
+interface laser_scanner {
+    ip: string;
+    port: number;
+    address: string;
+    frames: number;
+}
+
 
 interface MessagePayload {
     _type: 'info' | 'success' | 'warning' | 'error';
@@ -37,6 +53,20 @@ interface MessagePayload {
     message: string;
 }
 
+// 后端 AppError 序列化后的结构
+interface AppError {
+    code: string;
+    device: string;
+    message: string;
+}
+
+function errorMessage(err: unknown): string {
+    if (typeof err === 'object' && err !== null && 'message' in err) {
+        return String((err as AppError).message);
+    }
+    return String(err);
+}
+
 
 async function runInvoke<T>(
     cmd: string,
@@ -62,7 +92,7 @@ async function runInvoke<T>(
         // 默认错误处理
         NotificationPlugin.error({
             title: errorTitle ? errorTitle : "请求失败",
-            content: String(err_1),
+            content: errorMessage(err_1),
             placement: "top-right",
             duration: 3000,
             offset: [0, 0],
@@ -84,6 +114,8 @@ function RealTime() {
     const dataChart = useRef<echarts.ECharts | null>(null);
     const timerID = useRef<number | null>(null);
     const [form] = Form.useForm();
+    const [serialForm] = Form.useForm();
+    const [scanners, setScanners] = useState<laser_scanner[]>([]);
     const [angle, setAngle] = useState<number>(0.00);
     // const bandRateList: number[] = [
     //     110, 300, 600, 1200, 2400, 4800, 9600, 14400, 19200, 38400,
@@ -167,41 +199,34 @@ function RealTime() {
         setPortList(portVec);
     };
 
-    function useHallData(onData: (data: hall_data[]) => void) {
-        useEffect(() => {
-            // 每隔 200ms 执行一次
-            const timer = setInterval(async () => {
-                try {
-                    // 调用后端命令 fetch_hall_data
-                    const data = await invoke<hall_data[]>("fetch_hall_data");
-                    // 如果有数据就传给回调
-                    if (data.length > 0) {
-                        onData(data);
-                    }
-                } catch (e) {
-                    console.error("fetch error", e);
-                }
-            }, 1000);
-
-            // useEffect 的清理函数，组件卸载时清除定时器
-            return () => clearInterval(timer);
-        }, [onData]); // 依赖 onData 变化时重新执行
-    }
+    useEffect(() => {
+        // 插拔串口设备时后端推送新的串口列表
+        const unlisten = listen<serial_list>("ports_changed", (event) => {
+            updatePort(event.payload.port_vec);
+        });
+        return () => {
+            unlisten.then((f) => f());
+        };
+    }, []);
 
     useEffect(() => {
         let unlisten: (() => void) | undefined;
-        // 监听数据
-        listen<hall_data>("hall_recv", (event) => {
-            const newData = event.payload;
+        let unlistenHall: (() => void) | undefined;
+        // 后端每 100ms 把这段时间内的霍尔数据合并成一批推送
+        listen<hall_data[]>("hall_recv", (event) => {
             if (dataChart.current) {
-                newData.data.forEach((val, index) => {
-                    dataChart.current?.appendData({
-                        seriesIndex: index,
-                        data: [[newData.angle, val]],
+                event.payload.forEach((val) => {
+                    val.data.forEach((d, i) => {
+                        dataChart.current?.appendData({
+                            seriesIndex: i,
+                            data: [[val.angle, d]],
+                        });
                     });
                 });
             }
             dataChart.current?.resize();
+        }).then((fn) => {
+            unlistenHall = fn
         });
         listen<MessagePayload>("message", async (event) => {
             if (event.payload._type == 'error') {
@@ -243,22 +268,10 @@ function RealTime() {
         return () => {
             if (timerID.current) clearInterval(timerID.current);
             if (unlisten) unlisten(); // 组件卸载时移除监听
+            if (unlistenHall) unlistenHall();
             window.removeEventListener("resize", resizeHandler);
         };
     }, []);
-    useHallData((dataBatch) => {
-        if (dataChart.current) {
-            dataBatch.forEach((val) => {
-                val.data.forEach((d, i) => {
-                    dataChart.current?.appendData({
-                        seriesIndex: i,
-                        data: [[val.angle, d]],
-                    });
-                })
-            });
-        }
-        dataChart.current?.resize();
-    });
     return (
         <div style={{height: "100vh"}}>
             <div
@@ -328,6 +341,7 @@ function RealTime() {
             >
                 <Form
                     id="serialForm"
+                    form={serialForm}
                     labelWidth={120}
                     labelAlign={'left'}
                     initialData={{laserAddr: "192.168.2.3:43002"}}
@@ -379,6 +393,30 @@ function RealTime() {
                     <Form.FormItem label="激光传感器地址" name={"laserAddr"}>
                         <Input></Input>
                     </Form.FormItem>
+                    <Form.FormItem label="查找激光" help={"扫描仪不提供型号和序列号，查找结果只有应答的地址"}>
+                        <Space size={'small'}>
+                            <Select
+                                style={{width: '200px'}}
+                                placeholder={scanners.length ? "选择扫描仪" : "未查找"}
+                                onChange={(v) => serialForm.setFieldsValue({laserAddr: v})}
+                            >
+                                {scanners.map((s) => (
+                                    <Select.Option key={s.address} value={s.address} label={s.address}/>
+                                ))}
+                            </Select>
+                            <Button onClick={async () => {
+                                // 按当前地址中的端口广播查找
+                                const addr = String(serialForm.getFieldValue("laserAddr") ?? "");
+                                const port = Number(addr.split(":").pop());
+                                const res = await runInvoke<laser_scanner[]>("discover_lasers", {
+                                    port: Number.isInteger(port) && port > 0 ? port : null,
+                                }, "查找完成");
+                                setScanners(res);
+                            }}>
+                                查找
+                            </Button>
+                        </Space>
+                    </Form.FormItem>
                 </Form>
                 <Space direction={'vertical'} size={'medium'} style={{width: '100%', marginTop: '10px'}}>
                     <Form